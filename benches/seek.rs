@@ -0,0 +1,38 @@
+use std::io::{BufReader, Cursor};
+
+use cached_row_cursor::CachedRowCursor;
+
+fn main() {
+    divan::main();
+}
+
+// Cursor over `rows` rows of 8 bytes each, fully indexed at every row
+fn indexed_cursor(rows: u64) -> CachedRowCursor<BufReader<Cursor<Vec<u8>>>> {
+    let data = b"0123456\n".repeat(rows as usize);
+    let mut cursor = CachedRowCursor::new(BufReader::new(Cursor::new(data)), b'\n', 1);
+    cursor.build_index(|_, _| true).unwrap();
+    cursor
+}
+
+#[divan::bench(args = [1_000, 10_000, 100_000, 1_000_000])]
+fn set_position(bencher: divan::Bencher, rows: u64) {
+    let mut cursor = indexed_cursor(rows);
+    let length = rows * 8;
+    let mut pos = 0;
+
+    bencher.bench_local(|| {
+        pos = (pos + 7_919 * 8 + 3) % length;
+        cursor.set_position(pos).unwrap()
+    });
+}
+
+#[divan::bench(args = [1_000, 10_000, 100_000, 1_000_000])]
+fn set_position_to_end(bencher: divan::Bencher, rows: u64) {
+    let mut cursor = indexed_cursor(rows);
+    let length = rows * 8;
+
+    bencher.bench_local(|| {
+        cursor.set_position(0).unwrap();
+        cursor.set_position(length - 1).unwrap()
+    });
+}
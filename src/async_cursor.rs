@@ -0,0 +1,427 @@
+use std::io::SeekFrom;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncSeek, AsyncSeekExt};
+
+use crate::{
+    frame, CachedRowCursor, LengthHeader, LengthPrefixed, Mark, RowFramer, RowIndex, Separator,
+};
+
+// Async counterpart of `CachedRowCursor` over tokio readers. Both use the same
+// `RowIndex`, so an index built by one can be shared with the other.
+pub struct AsyncCachedRowCursor<T, F: RowFramer = Separator> {
+    // Positions and index kept as by the blocking cursor, over the async reader
+    cursor: CachedRowCursor<T, F>,
+}
+
+impl<T: AsyncBufRead + AsyncSeek + Unpin> AsyncCachedRowCursor<T> {
+    pub fn new<S: Into<Separator>>(reader: T, separator: S, granularity: u64) -> Self {
+        Self::with_framer(reader, separator.into(), granularity)
+    }
+}
+
+impl<T: AsyncBufRead + AsyncSeek + Unpin> AsyncCachedRowCursor<T, LengthPrefixed> {
+    // Create cursor over rows prefixed by their length, where `read_row`
+    // returns only the payload
+    pub fn with_length_prefix(reader: T, header: LengthHeader, granularity: u64) -> Self {
+        let mut cursor = Self::with_framer(reader, LengthPrefixed::new(header), granularity);
+        cursor.set_strip_framing(true);
+        cursor
+    }
+}
+
+impl<T: AsyncBufRead + AsyncSeek + Unpin, F: RowFramer> AsyncCachedRowCursor<T, F> {
+    // Create cursor with rows delimited by `framer`
+    pub fn with_framer(reader: T, framer: F, granularity: u64) -> Self {
+        Self {
+            cursor: CachedRowCursor::from_parts(reader, framer, RowIndex::shared(granularity)),
+        }
+    }
+
+    // Create cursor sharing `index` with other cursors over the same data and
+    // using the same framing. The cursor starts at the first data row.
+    pub async fn with_index(
+        mut reader: T,
+        framer: F,
+        index: Arc<RwLock<RowIndex>>,
+    ) -> Result<Self, std::io::Error> {
+        let origin = index.read().unwrap_or_else(PoisonError::into_inner).origin;
+        reader.seek(SeekFrom::Current(origin as i64)).await?;
+        Ok(Self {
+            cursor: CachedRowCursor::from_parts(reader, framer, index),
+        })
+    }
+
+    // Return the row index, which can be shared with other cursors through `with_index`
+    pub fn index(&self) -> &Arc<RwLock<RowIndex>> {
+        &self.cursor.index
+    }
+
+    // Return current byte position
+    pub fn position(&self) -> u64 {
+        self.cursor.pos
+    }
+
+    pub fn row_position(&self) -> u64 {
+        self.cursor.row_pos
+    }
+
+    // Set whether `read_row` returns rows without framing bytes, such as separators
+    pub fn set_strip_framing(&mut self, strip: bool) {
+        self.cursor.strip_framing = strip;
+    }
+
+    // Set whether the data may grow while it is read, as for
+    // `CachedRowCursor::set_follow`
+    pub fn set_follow(&mut self, follow: bool) {
+        self.cursor.set_follow(follow);
+    }
+
+    // Forget the recorded end of the data if bytes follow it, as for
+    // `CachedRowCursor::refresh`
+    pub async fn refresh(&mut self) -> Result<bool, std::io::Error> {
+        if self.cursor.lock_index().length.is_none() {
+            return Ok(false);
+        }
+
+        let inner = &mut self.cursor.inner;
+        let current = inner.stream_position().await?;
+        let end = inner.seek(SeekFrom::End(0)).await?;
+        inner.seek(SeekFrom::Start(current)).await?;
+
+        Ok(self.cursor.forget_end_if_grown(current, end))
+    }
+
+    // Set byte position
+    pub async fn set_position(&mut self, pos: u64) -> Result<u64, std::io::Error> {
+        let fixed_length = self.fixed_length().await?;
+        let (cached_byte, cached_row) = self.cursor.position_checkpoint(pos, fixed_length)?;
+        self.jump_to_checkpoint(cached_row, cached_byte).await?;
+
+        while self.cursor.pos < pos && self.scan_row(None, pos - self.cursor.pos).await? != 0 {}
+
+        Ok(self.cursor.pos)
+    }
+
+    pub async fn set_row_position(&mut self, row: u64) -> Result<u64, std::io::Error> {
+        let fixed_length = self.fixed_length().await?;
+        let (cached_byte, cached_row) = self.cursor.row_checkpoint(row, fixed_length);
+        self.jump_to_checkpoint(cached_row, cached_byte).await?;
+
+        while self.cursor.row_pos < row && self.scan_row(None, u64::MAX).await? != 0 {}
+
+        // In follow mode a partly written last row is left unread
+        if self.cursor.follow && self.cursor.partial {
+            let (row, byte) = (self.cursor.row_pos, self.cursor.row_start);
+            self.jump_to_checkpoint(row, byte).await?;
+        }
+
+        Ok(self.cursor.row_pos)
+    }
+
+    // Append the rest of the current row to `buf`, returning the number of bytes
+    // consumed, including framing bytes even if they are stripped
+    pub async fn read_row(&mut self, buf: &mut Vec<u8>) -> Result<usize, std::io::Error> {
+        let start = buf.len();
+        let mark = self.cursor.follow.then(|| self.cursor.mark());
+        let byte_len = self.scan_row(Some(buf), u64::MAX).await?;
+
+        // In follow mode a row is only read once it is complete
+        if let Some(mark) = mark.filter(|_| self.cursor.partial) {
+            buf.truncate(start);
+            self.restore(mark).await?;
+            return Ok(0);
+        }
+
+        self.cursor.strip_row(buf, start);
+        Ok(byte_len)
+    }
+
+    pub async fn seek_row(&mut self, pos: SeekFrom) -> Result<u64, std::io::Error> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => pos as i64,
+            SeekFrom::Current(pos) => self.cursor.row_pos as i64 + pos,
+            SeekFrom::End(pos) => {
+                if self.cursor.follow {
+                    self.refresh().await?;
+                }
+                self.build_index().await? as i64 - 1 + pos
+            }
+        };
+
+        if pos < 0 {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative row position",
+            ))
+        } else {
+            self.set_row_position(pos as u64).await
+        }
+    }
+
+    // Scan all rows after the last cached position to complete the row index,
+    // then return to the current position. Returns the total number of rows.
+    async fn build_index(&mut self) -> Result<u64, std::io::Error> {
+        if let Some(row_length) = self.cursor.lock_index().row_length {
+            return Ok(row_length);
+        }
+        if self.fixed_length().await?.is_some() {
+            return Ok(self.cursor.lock_index().row_length.unwrap());
+        }
+
+        let mark = self.cursor.mark();
+        let (cached_row, cached_byte) = self
+            .cursor
+            .lock_index()
+            .checkpoints()
+            .next_back()
+            .unwrap_or((0, 0));
+        self.jump_to_checkpoint(cached_row, cached_byte).await?;
+
+        let result = loop {
+            if let Some(row_length) = self.cursor.lock_index().row_length {
+                break Ok(row_length);
+            }
+            if let Err(e) = self.scan_row(None, u64::MAX).await {
+                break Err(e);
+            }
+        };
+
+        self.restore(mark).await?;
+        result
+    }
+
+    // Move to the start of a cached row
+    async fn jump_to_checkpoint(&mut self, row: u64, byte: u64) -> Result<(), std::io::Error> {
+        self.restore(Mark::checkpoint(row, byte)).await
+    }
+
+    // Return to a position saved by `mark`
+    async fn restore(&mut self, mark: Mark<F::State>) -> Result<(), std::io::Error> {
+        let offset = mark.pos as i64 - self.cursor.pos as i64;
+        self.cursor.inner.seek(SeekFrom::Current(offset)).await?;
+        self.cursor.set_mark(mark);
+        Ok(())
+    }
+
+    // Measure the data for fixed-length rows, returning its length, or `None`
+    // for other framers
+    async fn fixed_length(&mut self) -> Result<Option<u64>, std::io::Error> {
+        let Some(len) = self.cursor.framer.fixed_len() else {
+            return Ok(None);
+        };
+        if let Some(length) = self.cursor.lock_index().length {
+            return Ok(Some(length));
+        }
+
+        let inner = &mut self.cursor.inner;
+        let current = inner.stream_position().await?;
+        let end = inner.seek(SeekFrom::End(0)).await?;
+        inner.seek(SeekFrom::Start(current)).await?;
+
+        let remaining = end.saturating_sub(current);
+        Ok(Some(self.cursor.record_fixed_length(len, remaining)))
+    }
+
+    // Consume bytes up to the end of the current row, or at most `limit` bytes.
+    // Consumed bytes are appended to `buf` if given. Returns the number of
+    // bytes consumed.
+    async fn scan_row(
+        &mut self,
+        mut buf: Option<&mut Vec<u8>>,
+        limit: u64,
+    ) -> Result<usize, std::io::Error> {
+        let mut byte_len = 0;
+
+        while (byte_len as u64) < limit {
+            match self
+                .step(buf.as_deref_mut(), limit - byte_len as u64)
+                .await?
+            {
+                Some((used, row_end)) => {
+                    byte_len += used;
+                    if row_end {
+                        return Ok(byte_len);
+                    }
+                }
+                None => {
+                    self.cursor.reach_eof()?;
+                    return Ok(byte_len);
+                }
+            }
+        }
+
+        // A row stopped at `limit` may already be complete, depending on what follows
+        let cursor = &mut self.cursor;
+        if cursor.partial {
+            let available = cursor.inner.fill_buf().await?;
+            let at_eof = available.is_empty();
+            let state = &mut cursor.state.clone();
+            let row_end = matches!(
+                frame(&cursor.framer, state, available, None, u64::MAX),
+                Ok((0, true))
+            );
+            cursor.stop_at_limit(at_eof, row_end)?;
+        }
+
+        Ok(byte_len)
+    }
+
+    // Consume buffered bytes up to the next row end, at most `limit` bytes.
+    // Returns the number of bytes consumed and whether they end a row, or
+    // `None` at EOF.
+    async fn step(
+        &mut self,
+        buf: Option<&mut Vec<u8>>,
+        limit: u64,
+    ) -> Result<Option<(usize, bool)>, std::io::Error> {
+        let cursor = &mut self.cursor;
+        let (used, row_end) = loop {
+            let available = match cursor.inner.fill_buf().await {
+                Ok(available) => available,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if available.is_empty() {
+                return Ok(None);
+            }
+            break frame(&cursor.framer, &mut cursor.state, available, buf, limit)?;
+        };
+
+        cursor.inner.consume(used);
+        cursor.consumed(used, row_end);
+        Ok(Some((used, row_end)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AsyncCachedRowCursor;
+    use crate::{CachedRowCursor, FixedLength, LengthHeader, Separator};
+    use std::future::Future;
+    use std::io::{Cursor, SeekFrom};
+    use tokio::io::BufReader;
+
+    fn block_on<R>(future: impl Future<Output = R>) -> R {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn same_as_blocking() {
+        let cases: [(&[u8], Separator); 4] = [
+            (b"foo\nbar\nbiz\nbaz\nbuz\n", Separator::from(b'\n')),
+            (b"foo\nbar\n\n\nbiz", Separator::from(b'\n')),
+            (b"\r\nfoo\r\r\n\r\nbar\rbiz\r\n\r", Separator::from(b"\r\n")),
+            (b"a\nb\r\nc\rd\r\r\n\n\re\r", Separator::universal_newline()),
+        ];
+
+        for (data, separator) in cases {
+            let reader = std::io::BufReader::new(Cursor::new(data));
+            let mut expected = CachedRowCursor::new(reader, separator.clone(), 2);
+            let reader = BufReader::with_capacity(3, Cursor::new(data));
+            let mut cursor = AsyncCachedRowCursor::new(reader, separator, 2);
+
+            block_on(async {
+                for row in [3, 1, 0, 5, 2, 9] {
+                    let found = cursor.set_row_position(row).await.unwrap();
+                    assert_eq!(found, expected.set_row_position(row).unwrap());
+                    assert_eq!(cursor.position(), expected.position());
+
+                    let (mut buf, mut expected_buf) = (vec![], vec![]);
+                    let len = cursor.read_row(&mut buf).await.unwrap();
+                    assert_eq!(len, expected.read_row(&mut expected_buf).unwrap());
+                    assert_eq!(buf, expected_buf);
+                }
+
+                for pos in [7, 2, 0, 11, 30] {
+                    let found = cursor.set_position(pos).await.unwrap();
+                    assert_eq!(found, expected.set_position(pos).unwrap());
+                    assert_eq!(cursor.row_position(), expected.row_position());
+                }
+
+                for pos in [SeekFrom::End(0), SeekFrom::Current(-2), SeekFrom::End(-1)] {
+                    let found = cursor.seek_row(pos).await.unwrap();
+                    assert_eq!(found, expected.seek_row(pos).unwrap());
+                    assert_eq!(cursor.position(), expected.position());
+                }
+            });
+            assert_eq!(*cursor.index().read().unwrap(), *expected.lock_index());
+        }
+    }
+
+    #[test]
+    fn shared_index() {
+        let data = b"id\nfoo\nbar\nbiz\nbaz\n";
+        let reader = std::io::BufReader::new(Cursor::new(data));
+        let blocking = CachedRowCursor::new(reader, b'\n', 2);
+        let blocking = blocking.with_header_rows(1).unwrap();
+
+        block_on(async {
+            let reader = BufReader::new(Cursor::new(data));
+            let index = blocking.index().clone();
+            let mut cursor =
+                AsyncCachedRowCursor::with_index(reader, Separator::from(b'\n'), index)
+                    .await
+                    .unwrap();
+            assert_eq!(cursor.position(), 3);
+            assert_eq!(cursor.seek_row(SeekFrom::End(0)).await.unwrap(), 3);
+            cursor.set_strip_framing(true);
+            let mut buf = vec![];
+            cursor.read_row(&mut buf).await.unwrap();
+            assert_eq!(buf, b"baz");
+        });
+
+        // Rows found by the async cursor are known to the blocking one
+        assert_eq!(blocking.offset_of_row(2), Some(11));
+        assert_eq!(blocking.offset_of_row(4), Some(19));
+    }
+
+    #[test]
+    fn framers() {
+        block_on(async {
+            let reader = BufReader::new(Cursor::new(b"\x03foo\x00\x02ab"));
+            let mut cursor = AsyncCachedRowCursor::with_length_prefix(reader, LengthHeader::U8, 1);
+            assert_eq!(cursor.set_row_position(2).await.unwrap(), 2);
+            let mut buf = vec![];
+            assert_eq!(cursor.read_row(&mut buf).await.unwrap(), 3);
+            assert_eq!(buf, b"ab");
+
+            let reader = BufReader::new(Cursor::new(b"foobarbiz"));
+            let mut cursor = AsyncCachedRowCursor::with_framer(reader, FixedLength::new(3), 1);
+            assert_eq!(cursor.seek_row(SeekFrom::End(0)).await.unwrap(), 2);
+            assert_eq!(cursor.position(), 6);
+            assert_eq!(cursor.set_position(4).await.unwrap(), 4);
+            assert_eq!(cursor.row_position(), 1);
+        });
+    }
+
+    #[test]
+    fn follow() {
+        block_on(async {
+            let reader = BufReader::new(Cursor::new(b"foo\nbar\nbi".to_vec()));
+            let mut cursor = AsyncCachedRowCursor::new(reader, b'\n', 1);
+            cursor.set_follow(true);
+            assert_eq!(cursor.set_row_position(5).await.unwrap(), 2);
+            assert_eq!(cursor.position(), 8);
+            let mut buf = vec![];
+            assert_eq!(cursor.read_row(&mut buf).await.unwrap(), 0);
+            assert_eq!(cursor.seek_row(SeekFrom::End(0)).await.unwrap(), 1);
+
+            // Seeks from the end find rows written since
+            let data = cursor.cursor.inner.get_mut().get_mut();
+            data.extend_from_slice(b"z\nbaz\n");
+            assert_eq!(cursor.seek_row(SeekFrom::End(0)).await.unwrap(), 3);
+            assert_eq!(cursor.position(), 12);
+
+            let reader = BufReader::new(Cursor::new(b"foobarb"));
+            let mut cursor = AsyncCachedRowCursor::with_framer(reader, FixedLength::new(3), 1);
+            cursor.set_follow(true);
+            assert_eq!(cursor.seek_row(SeekFrom::End(0)).await.unwrap(), 1);
+            assert_eq!(cursor.set_row_position(3).await.unwrap(), 2);
+            assert_eq!(cursor.position(), 6);
+        });
+    }
+}
@@ -0,0 +1,222 @@
+use std::io::{BufRead, Seek};
+
+use crate::{CachedRowCursor, RowFramer};
+
+// Unit columns are counted in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnUnit {
+    Byte,
+    // Unicode scalar values of UTF-8 text
+    Utf8Char,
+    // UTF-16 code units, as used by the Language Server Protocol by default
+    Utf16,
+}
+
+impl ColumnUnit {
+    // Number of columns taken by the UTF-8 sequence starting with `b`, or 0 for
+    // a continuation byte
+    fn units(self, b: u8) -> u64 {
+        match self {
+            Self::Byte => 1,
+            _ if b & 0xc0 == 0x80 => 0,
+            Self::Utf8Char => 1,
+            // Characters outside the BMP take a surrogate pair
+            Self::Utf16 if b >= 0xf0 => 2,
+            Self::Utf16 => 1,
+        }
+    }
+
+    // Number of columns taken by `bytes`
+    fn width(self, bytes: &[u8]) -> u64 {
+        bytes.iter().map(|&b| self.units(b)).sum()
+    }
+
+    // Number of leading bytes of `bytes` taking `column` columns, or all of them
+    fn byte_len(self, bytes: &[u8], column: u64) -> usize {
+        let mut columns = 0;
+        for (i, &b) in bytes.iter().enumerate() {
+            let units = self.units(b);
+            if units > 0 && columns >= column {
+                return i;
+            }
+            columns += units;
+        }
+        bytes.len()
+    }
+}
+
+impl<T: BufRead + Seek, F: RowFramer> CachedRowCursor<T, F> {
+    // Convert byte offset `byte` to a row and a column within the row contents,
+    // or `None` outside the data rows. The cursor position is unchanged.
+    pub fn row_column(
+        &mut self,
+        byte: u64,
+        unit: ColumnUnit,
+    ) -> Result<Option<(u64, u64)>, std::io::Error> {
+        let Some(row) = self.find_row_at_offset(byte)? else {
+            return self.end_row_column(byte, unit);
+        };
+        let Some((start, bytes)) = self.read_whole_row(row)? else {
+            return Ok(None);
+        };
+
+        // Offsets in framing bytes are clamped to the row contents
+        let payload = self.framer.payload(&bytes);
+        let end = ((byte - start) as usize).clamp(payload.start, payload.end);
+        Ok(Some((row, unit.width(&bytes[payload.start..end]))))
+    }
+
+    // Convert a row and a column within the row contents to a byte offset, or
+    // `None` past the last row. Columns past the end of the contents are
+    // clamped to it. The cursor position is unchanged.
+    pub fn offset_of_row_column(
+        &mut self,
+        row: u64,
+        column: u64,
+        unit: ColumnUnit,
+    ) -> Result<Option<u64>, std::io::Error> {
+        let Some((start, bytes)) = self.read_whole_row(row)? else {
+            return Ok(None);
+        };
+
+        let payload = self.framer.payload(&bytes);
+        let len = unit.byte_len(&bytes[payload.clone()], column);
+        Ok(Some(start + (payload.start + len) as u64))
+    }
+
+    // Convert the end of the data to the end of the last row, or to the start
+    // of the row after it if the last row ends with framing bytes, so that
+    // `offset_of_row_column` maps it back. Other offsets give `None`.
+    fn end_row_column(
+        &mut self,
+        byte: u64,
+        unit: ColumnUnit,
+    ) -> Result<Option<(u64, u64)>, std::io::Error> {
+        if byte < self.lock_index().origin {
+            return Ok(None);
+        }
+        let Some(row_length) = self.build_index(|_, _| true)? else {
+            return Ok(None);
+        };
+        if self.lock_index().length != Some(byte) {
+            return Ok(None);
+        }
+
+        if let Some(last) = row_length.checked_sub(1) {
+            if let Some((_, bytes)) = self.read_whole_row(last)? {
+                let payload = self.framer.payload(&bytes);
+                if payload.end == bytes.len() {
+                    return Ok(Some((last, unit.width(&bytes[payload]))));
+                }
+            }
+        }
+        Ok(Some((row_length, 0)))
+    }
+
+    // Return the start and bytes of row `row` and return to the current position
+    fn read_whole_row(&mut self, row: u64) -> Result<Option<(u64, Vec<u8>)>, std::io::Error> {
+        let mark = self.mark();
+        let result = self.set_row_position(row).and_then(|found| {
+            if found != row {
+                return Ok(None);
+            }
+            let start = self.pos;
+            let mut bytes = vec![];
+            self.scan_row(Some(&mut bytes), u64::MAX)?;
+            Ok(Some((start, bytes)))
+        });
+        self.restore(mark)?;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ColumnUnit;
+    use crate::{CachedRowCursor, LengthHeader};
+    use std::io::{BufReader, Cursor};
+
+    #[test]
+    fn units() {
+        let text = "aé€😀b".as_bytes();
+        assert_eq!(ColumnUnit::Byte.width(text), 11);
+        assert_eq!(ColumnUnit::Utf8Char.width(text), 5);
+        assert_eq!(ColumnUnit::Utf16.width(text), 6);
+
+        assert_eq!(ColumnUnit::Byte.byte_len(text, 4), 4);
+        assert_eq!(ColumnUnit::Utf8Char.byte_len(text, 4), 10);
+        assert_eq!(ColumnUnit::Utf16.byte_len(text, 3), 6);
+        assert_eq!(ColumnUnit::Utf16.byte_len(text, 5), 10);
+        assert_eq!(ColumnUnit::Utf16.byte_len(text, 9), 11);
+    }
+
+    #[test]
+    fn row_column() {
+        use ColumnUnit::{Byte, Utf16, Utf8Char};
+
+        let data = "foo\r\nbär😀z\r\nbiz".as_bytes();
+        let reader = BufReader::with_capacity(4, Cursor::new(data));
+        let mut cursor = CachedRowCursor::new(reader, b"\r\n", 2);
+        cursor.set_position(2).unwrap();
+
+        let mut row_column = |byte, unit| cursor.row_column(byte, unit).unwrap();
+        assert_eq!(row_column(1, Byte), Some((0, 1)));
+        assert_eq!(row_column(4, Byte), Some((0, 3)));
+        assert_eq!(row_column(5, Utf16), Some((1, 0)));
+        assert_eq!(row_column(9, Byte), Some((1, 4)));
+        assert_eq!(row_column(9, Utf8Char), Some((1, 3)));
+        assert_eq!(row_column(13, Utf8Char), Some((1, 4)));
+        assert_eq!(row_column(13, Utf16), Some((1, 5)));
+        assert_eq!(row_column(18, Utf8Char), Some((2, 2)));
+        assert_eq!(row_column(19, Byte), Some((2, 3)));
+        assert_eq!(row_column(20, Byte), None);
+
+        let mut offset =
+            |row, column, unit| cursor.offset_of_row_column(row, column, unit).unwrap();
+        assert_eq!(offset(1, 4, Utf8Char), Some(13));
+        assert_eq!(offset(1, 5, Utf16), Some(13));
+        assert_eq!(offset(1, 9, Utf16), Some(14));
+        assert_eq!(offset(2, 2, Byte), Some(18));
+        assert_eq!(offset(3, 0, Byte), Some(19));
+        assert_eq!(offset(4, 0, Byte), None);
+
+        assert_eq!(cursor.position(), 2);
+        assert_eq!(cursor.row_position(), 0);
+    }
+
+    #[test]
+    fn length_prefixed() {
+        let data = BufReader::new(Cursor::new(b"\x03foo\x02ab"));
+        let mut cursor = CachedRowCursor::with_length_prefix(data, LengthHeader::U8, 1);
+
+        let mut row_column = |byte| cursor.row_column(byte, ColumnUnit::Byte).unwrap();
+        assert_eq!(row_column(0), Some((0, 0)));
+        assert_eq!(row_column(4), Some((1, 0)));
+        assert_eq!(row_column(6), Some((1, 1)));
+        assert_eq!(row_column(7), Some((1, 2)));
+        let offset = cursor.offset_of_row_column(1, 1, ColumnUnit::Byte);
+        assert_eq!(offset.unwrap(), Some(6));
+    }
+
+    #[test]
+    fn end_of_data() {
+        for (data, end) in [
+            (&b"foo\nbar\n"[..], (2, 0)),
+            (b"foo\nbar", (1, 3)),
+            (b"", (0, 0)),
+        ] {
+            let reader = BufReader::new(Cursor::new(data));
+            let mut cursor = CachedRowCursor::new(reader, b'\n', 1);
+
+            let byte = data.len() as u64;
+            let row_column = cursor.row_column(byte, ColumnUnit::Utf16).unwrap();
+            assert_eq!(row_column, Some(end));
+            let offset = cursor.offset_of_row_column(end.0, end.1, ColumnUnit::Utf16);
+            assert_eq!(offset.unwrap(), Some(byte));
+            assert_eq!(
+                cursor.row_column(byte + 1, ColumnUnit::Utf16).unwrap(),
+                None
+            );
+        }
+    }
+}
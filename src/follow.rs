@@ -0,0 +1,270 @@
+use std::io::{BufRead, Seek, SeekFrom};
+use std::time::Duration;
+
+use crate::{frame, CachedRowCursor, RowFramer};
+
+impl<T, F: RowFramer> CachedRowCursor<T, F> {
+    // Set whether the data may grow while it is read, like a log being
+    // written. In follow mode a last row without a trailing row end is taken
+    // to be still written: it is not counted as a row, `read_row` returns 0
+    // instead of reading it, the `Read` and `BufRead` impls stop before it,
+    // and the data is taken to end before it. Rows written later are found as
+    // the cursor reads on, and `refresh` forgets the recorded end of the data
+    // once it has grown, as seeks from the end do first.
+    //
+    // Follow mode should be set before the end of the data is reached, as a
+    // partly written last row read before is counted as a row.
+    pub fn set_follow(&mut self, follow: bool) {
+        if follow != self.follow {
+            self.forget_end();
+        }
+        self.follow = follow;
+    }
+
+    pub fn follow(&self) -> bool {
+        self.follow
+    }
+
+    // Forget the recorded end of the data if it has grown, given the reader
+    // position and the end of the data. Returns whether the end was forgotten.
+    pub(crate) fn forget_end_if_grown(&mut self, current: u64, end: u64) -> bool {
+        let grown = self
+            .lock_index()
+            .length
+            .is_some_and(|length| self.pos + end.saturating_sub(current) > length);
+        if grown {
+            self.forget_end();
+        }
+        grown
+    }
+
+    fn forget_end(&mut self) {
+        let mut index = self.lock_index_mut();
+        index.length = None;
+        index.row_length = None;
+    }
+}
+
+impl<T: BufRead + Seek, F: RowFramer> CachedRowCursor<T, F> {
+    // Forget the recorded end of the data if bytes follow it, such as rows
+    // written since or a partly written last row, so that row counts and seeks
+    // from the end are found again. Returns whether the end was forgotten.
+    // This seeks the reader, discarding its buffer, so it is best called once
+    // the cursor has read to the end.
+    pub fn refresh(&mut self) -> Result<bool, std::io::Error> {
+        if self.lock_index().length.is_none() {
+            return Ok(false);
+        }
+
+        let current = self.inner.stream_position()?;
+        let end = self.inner.seek(SeekFrom::End(0))?;
+        self.inner.seek(SeekFrom::Start(current))?;
+
+        Ok(self.forget_end_if_grown(current, end))
+    }
+
+    // Fill the buffer for the `BufRead` impl in follow mode, up to the end of
+    // the rows known to be complete. A row running past the buffer is scanned
+    // ahead to find whether it is complete.
+    pub(crate) fn fill_complete_rows(&mut self) -> Result<&[u8], std::io::Error> {
+        if self.complete_end <= self.pos {
+            let available = self.inner.fill_buf()?;
+            let state = &mut self.state.clone();
+            match frame(&self.framer, state, available, None, u64::MAX)? {
+                (used, true) => self.complete_end = self.pos + used as u64,
+                _ => {
+                    let mark = self.mark();
+                    self.scan_row(None, u64::MAX)?;
+                    self.restore(mark)?;
+                }
+            }
+        }
+
+        let available = self.inner.fill_buf()?;
+        let complete = self.complete_end.saturating_sub(self.pos);
+        Ok(&available[..complete.min(available.len() as u64) as usize])
+    }
+
+    // Append the next complete row to `buf` in follow mode, waiting for it to
+    // be written if needed by checking the data every `interval`. Returns the
+    // number of bytes consumed.
+    pub fn next_row(
+        &mut self,
+        buf: &mut Vec<u8>,
+        interval: Duration,
+    ) -> Result<usize, std::io::Error> {
+        if !self.follow {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "next_row requires follow mode",
+            ));
+        }
+
+        loop {
+            let byte_len = self.read_row(buf)?;
+            if byte_len != 0 {
+                return Ok(byte_len);
+            }
+            std::thread::sleep(interval);
+            self.refresh()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::temp_file;
+    use crate::{CachedRowCursor, FixedLength};
+    use std::fs::OpenOptions;
+    use std::io::{BufRead, BufReader, Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+    use std::time::Duration;
+
+    #[test]
+    fn growing_data() {
+        let data = BufReader::with_capacity(3, Cursor::new(b"foo\nbar\nbi".to_vec()));
+        let mut cursor = CachedRowCursor::new(data, b'\n', 2);
+        cursor.set_follow(true);
+
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 4);
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 4);
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 0);
+        assert_eq!(buf, b"foo\nbar\n");
+        assert_eq!(cursor.position(), 8);
+        assert_eq!(cursor.lock_index().row_length, Some(2));
+        assert_eq!(cursor.lock_index().length, Some(8));
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 1);
+        assert_eq!(cursor.set_row_position(5).unwrap(), 2);
+        assert_eq!(cursor.position(), 8);
+        // The partly written row follows the recorded end
+        assert!(cursor.refresh().unwrap());
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 1);
+
+        cursor
+            .inner
+            .get_mut()
+            .get_mut()
+            .extend_from_slice(b"z\nbaz\nb");
+        assert!(cursor.refresh().unwrap());
+        assert_eq!(cursor.lock_index().row_length, None);
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 3);
+        assert_eq!(cursor.position(), 12);
+        assert_eq!(
+            cursor.lock_index().checkpoints().collect::<Vec<_>>(),
+            [(0, 0), (2, 8), (4, 16)]
+        );
+
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 4);
+        assert_eq!(buf, b"baz\n");
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 0);
+        assert_eq!(cursor.position(), 16);
+
+        cursor.inner.get_mut().get_mut().extend_from_slice(b"uz\n");
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 4);
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 0);
+        assert!(!cursor.refresh().unwrap());
+        assert_eq!(cursor.lock_index().row_length, Some(5));
+    }
+
+    #[test]
+    fn seek_from_end() {
+        let data = BufReader::new(Cursor::new(b"foo\nbar\n".to_vec()));
+        let mut cursor = CachedRowCursor::new(data, b'\n', 1);
+        cursor.set_follow(true);
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 1);
+
+        // Rows written since are found without calling `refresh`
+        cursor
+            .inner
+            .get_mut()
+            .get_mut()
+            .extend_from_slice(b"biz\nbaz\nb");
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 3);
+        assert_eq!(cursor.position(), 12);
+
+        cursor.inner.get_mut().get_mut().extend_from_slice(b"uz\n");
+        assert_eq!(cursor.seek(SeekFrom::End(0)).unwrap(), 19);
+        assert_eq!(cursor.row_position(), 4);
+    }
+
+    #[test]
+    fn trait_reads() {
+        let data = BufReader::with_capacity(3, Cursor::new(b"foo\nba".to_vec()));
+        let mut cursor = CachedRowCursor::new(data, b'\n', 1);
+        cursor.set_follow(true);
+
+        // The partly written row is not read
+        let mut buf = vec![];
+        assert_eq!(cursor.read_to_end(&mut buf).unwrap(), 4);
+        assert_eq!(buf, b"foo\n");
+        assert_eq!(cursor.position(), 4);
+        assert_eq!(cursor.lock_index().length, Some(4));
+        assert_eq!(cursor.seek(SeekFrom::End(0)).unwrap(), 3);
+        assert_eq!(cursor.seek(SeekFrom::Current(1)).unwrap(), 4);
+        assert_eq!(cursor.fill_buf().unwrap(), b"");
+
+        cursor.inner.get_mut().get_mut().extend_from_slice(b"r\nbi");
+        assert!(cursor.refresh().unwrap());
+        let mut buf = vec![];
+        assert_eq!(cursor.read_until(b'\n', &mut buf).unwrap(), 4);
+        assert_eq!(buf, b"bar\n");
+        assert_eq!(cursor.read_until(b'\n', &mut buf).unwrap(), 0);
+        assert_eq!(cursor.position(), 8);
+        assert_eq!(cursor.row_position(), 2);
+        assert_eq!(cursor.seek(SeekFrom::End(0)).unwrap(), 7);
+    }
+
+    #[test]
+    fn fixed_length() {
+        let data = BufReader::new(Cursor::new(b"foobarb".to_vec()));
+        let mut cursor = CachedRowCursor::with_framer(data, FixedLength::new(3), 1);
+        cursor.set_follow(true);
+
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 1);
+        assert_eq!(cursor.lock_index().length, Some(6));
+        assert_eq!(cursor.set_row_position(2).unwrap(), 2);
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 0);
+
+        cursor.inner.get_mut().get_mut().extend_from_slice(b"iz");
+        assert!(cursor.refresh().unwrap());
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 3);
+        assert_eq!(buf, b"biz");
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 2);
+    }
+
+    #[test]
+    fn next_row() {
+        let path = temp_file("follow", b"foo\nba");
+
+        let file = std::fs::File::open(&path).unwrap();
+        let mut cursor = CachedRowCursor::new(BufReader::new(file), b'\n', 1);
+        let mut buf = vec![];
+        let err = cursor.next_row(&mut buf, Duration::ZERO).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        cursor.set_follow(true);
+
+        let writer = std::thread::spawn({
+            let path = path.clone();
+            move || {
+                let mut file = OpenOptions::new().append(true).open(path).unwrap();
+                for part in [&b"r"[..], b"\nbi", b"z\n"] {
+                    std::thread::sleep(Duration::from_millis(20));
+                    file.write_all(part).unwrap();
+                }
+            }
+        });
+
+        let interval = Duration::from_millis(5);
+        for expected in [&b"foo\n"[..], b"bar\n", b"biz\n"] {
+            let mut buf = vec![];
+            assert_eq!(cursor.next_row(&mut buf, interval).unwrap(), 4);
+            assert_eq!(buf, expected);
+        }
+        writer.join().unwrap();
+        assert_eq!(cursor.row_position(), 3);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,332 @@
+use std::ops::Range;
+
+// Decides where each row ends in a stream of bytes
+pub trait RowFramer {
+    // Scanning state of a partially read row, carried across buffers
+    type State: Clone + Default;
+
+    // Search `buf` for the end of the current row, continuing from `state`.
+    // Returns the index just past the end of the row, which is 0 when the row
+    // was already consumed but only confirmed by the first byte of `buf`; a row
+    // with no bytes consumed must not end at 0. `state` is reset to its default
+    // once a row ends.
+    fn find_row_end(
+        &self,
+        state: &mut Self::State,
+        buf: &[u8],
+    ) -> Result<Option<usize>, std::io::Error>;
+
+    // Return the range of a complete row holding its contents without framing bytes
+    fn payload(&self, row: &[u8]) -> Range<usize> {
+        0..row.len()
+    }
+
+    // Check a last row cut off by the end of input, which is counted as a row
+    // unless this returns an error
+    fn finish(&self, _state: &Self::State) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    // Length of every row if all rows have the same length, so rows can be
+    // located by arithmetic instead of scanning. Only the last row may be shorter.
+    fn fixed_len(&self) -> Option<u64> {
+        None
+    }
+}
+
+// Rows of a fixed number of bytes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedLength {
+    len: u64,
+}
+
+impl FixedLength {
+    pub fn new(len: u64) -> Self {
+        assert!(len > 0, "record length must not be zero");
+        Self { len }
+    }
+
+    pub fn record_len(&self) -> u64 {
+        self.len
+    }
+}
+
+impl RowFramer for FixedLength {
+    // Bytes of the current row seen so far
+    type State = u64;
+
+    fn find_row_end(&self, seen: &mut u64, buf: &[u8]) -> Result<Option<usize>, std::io::Error> {
+        let needed = self.len - *seen;
+        if needed <= buf.len() as u64 {
+            *seen = 0;
+            Ok(Some(needed as usize))
+        } else {
+            *seen += buf.len() as u64;
+            Ok(None)
+        }
+    }
+
+    fn fixed_len(&self) -> Option<u64> {
+        Some(self.len)
+    }
+}
+
+// Rows made of a payload length header followed by the payload
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LengthPrefixed {
+    header: LengthHeader,
+}
+
+// Encoding of the payload length at the start of each row
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LengthHeader {
+    U8,
+    U16Le,
+    U16Be,
+    #[default]
+    U32Le,
+    U32Be,
+    U64Le,
+    U64Be,
+    // Unsigned LEB128, as used by protobuf
+    Varint,
+}
+
+// Longest LEB128 encoding of a `u64`
+const MAX_VARINT_LEN: usize = 10;
+
+impl LengthHeader {
+    // Width of the header, or `None` for varints
+    fn width(self) -> Option<usize> {
+        match self {
+            Self::U8 => Some(1),
+            Self::U16Le | Self::U16Be => Some(2),
+            Self::U32Le | Self::U32Be => Some(4),
+            Self::U64Le | Self::U64Be => Some(8),
+            Self::Varint => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LengthPrefixedState {
+    // Length decoded from the first `seen` header bytes
+    Header { value: u64, seen: usize },
+    Payload { remaining: u64 },
+}
+
+impl Default for LengthPrefixedState {
+    fn default() -> Self {
+        Self::Header { value: 0, seen: 0 }
+    }
+}
+
+impl LengthPrefixed {
+    pub fn new(header: LengthHeader) -> Self {
+        Self { header }
+    }
+
+    pub fn header(&self) -> LengthHeader {
+        self.header
+    }
+
+    // Add the next header byte to `value`, returning whether the header is complete
+    fn decode(&self, value: &mut u64, seen: usize, b: u8) -> Result<bool, std::io::Error> {
+        let b = b as u64;
+        match self.header {
+            LengthHeader::U8 | LengthHeader::U16Le | LengthHeader::U32Le | LengthHeader::U64Le => {
+                *value |= b << (8 * seen)
+            }
+            LengthHeader::U16Be | LengthHeader::U32Be | LengthHeader::U64Be => {
+                *value = *value << 8 | b
+            }
+            LengthHeader::Varint => {
+                if seen == MAX_VARINT_LEN - 1 && b > 1 {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "varint row length overflows u64",
+                    ));
+                }
+                *value |= (b & 0x7f) << (7 * seen);
+                return Ok(b & 0x80 == 0);
+            }
+        }
+        Ok(Some(seen + 1) == self.header.width())
+    }
+}
+
+impl RowFramer for LengthPrefixed {
+    type State = LengthPrefixedState;
+
+    fn find_row_end(
+        &self,
+        state: &mut LengthPrefixedState,
+        buf: &[u8],
+    ) -> Result<Option<usize>, std::io::Error> {
+        let mut i = 0;
+        loop {
+            match state {
+                LengthPrefixedState::Header { value, seen } => {
+                    let Some(&b) = buf.get(i) else {
+                        return Ok(None);
+                    };
+                    i += 1;
+                    if self.decode(value, *seen, b)? {
+                        let remaining = *value;
+                        *state = LengthPrefixedState::Payload { remaining };
+                    } else {
+                        *seen += 1;
+                    }
+                }
+                LengthPrefixedState::Payload { remaining } => {
+                    let available = (buf.len() - i) as u64;
+                    if *remaining <= available {
+                        let end = i + *remaining as usize;
+                        *state = LengthPrefixedState::default();
+                        return Ok(Some(end));
+                    }
+                    *remaining -= available;
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    fn payload(&self, row: &[u8]) -> Range<usize> {
+        let header_len = match self.header.width() {
+            Some(width) => width,
+            None => row
+                .iter()
+                .position(|b| b & 0x80 == 0)
+                .map_or(row.len(), |i| i + 1),
+        };
+        header_len.min(row.len())..row.len()
+    }
+
+    fn finish(&self, _state: &LengthPrefixedState) -> Result<(), std::io::Error> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "length-prefixed row runs past end of input",
+        ))
+    }
+}
+
+// CSV records as in RFC 4180, where newlines inside quoted fields do not end
+// a record. Records end at `\n`, which may be preceded by `\r`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Csv;
+
+impl RowFramer for Csv {
+    // Whether the current position is inside a quoted field. An escaped quote
+    // `""` leaves and re-enters the quoted field, so it needs no extra state.
+    type State = bool;
+
+    fn find_row_end(
+        &self,
+        in_quotes: &mut bool,
+        buf: &[u8],
+    ) -> Result<Option<usize>, std::io::Error> {
+        let mut i = 0;
+        while let Some(j) = memchr::memchr2(b'"', b'\n', &buf[i..]) {
+            i += j + 1;
+            if buf[i - 1] == b'"' {
+                *in_quotes = !*in_quotes;
+            } else if !*in_quotes {
+                return Ok(Some(i));
+            }
+        }
+        Ok(None)
+    }
+
+    fn payload(&self, row: &[u8]) -> Range<usize> {
+        let end = match row {
+            [.., b'\r', b'\n'] => row.len() - 2,
+            [.., b'\n'] => row.len() - 1,
+            _ => row.len(),
+        };
+        0..end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Csv, FixedLength, LengthHeader, LengthPrefixed, RowFramer};
+
+    fn row_ends<F: RowFramer>(framer: &F, chunks: &[&[u8]]) -> Vec<usize> {
+        let mut state = F::State::default();
+        let mut ends = vec![];
+        let mut offset = 0;
+        for chunk in chunks {
+            let mut i = 0;
+            while let Some(end) = framer.find_row_end(&mut state, &chunk[i..]).unwrap() {
+                i += end;
+                ends.push(offset + i);
+            }
+            offset += chunk.len();
+        }
+        ends
+    }
+
+    #[test]
+    fn fixed_length() {
+        let framer = FixedLength::new(3);
+        assert_eq!(row_ends(&framer, &[b"abcdefgh"]), [3, 6]);
+        assert_eq!(row_ends(&framer, &[b"ab", b"cd", b"efgh", b"i"]), [3, 6, 9]);
+        assert_eq!(framer.payload(b"abc"), 0..3);
+    }
+
+    #[test]
+    fn length_prefixed() {
+        let framer = LengthPrefixed::default();
+        let data = b"\x03\0\0\0foo\0\0\0\0\x02\0\0\0ba";
+        assert_eq!(row_ends(&framer, &[data]), [7, 11, 17]);
+        assert_eq!(
+            row_ends(
+                &framer,
+                &[&data[..2], &data[2..5], &data[5..12], &data[12..]]
+            ),
+            [7, 11, 17]
+        );
+        assert_eq!(framer.payload(b"\x03\0\0\0foo"), 4..7);
+
+        let framer = LengthPrefixed::new(LengthHeader::U16Be);
+        assert_eq!(row_ends(&framer, &[b"\0\x03foo\0", b"\0\0"]), [5, 7]);
+        assert_eq!(framer.payload(b"\0\x03foo"), 2..5);
+
+        let framer = LengthPrefixed::new(LengthHeader::U8);
+        assert_eq!(row_ends(&framer, &[b"\x01a\0\x02bc"]), [2, 3, 6]);
+    }
+
+    #[test]
+    fn varint() {
+        let framer = LengthPrefixed::new(LengthHeader::Varint);
+        let mut data = vec![0x82, 0x01];
+        data.extend_from_slice(&[b'x'; 130]);
+        data.extend_from_slice(b"\x02ab\0");
+        assert_eq!(row_ends(&framer, &[&data]), [132, 135, 136]);
+        assert_eq!(
+            row_ends(&framer, &[&data[..1], &data[1..]]),
+            [132, 135, 136]
+        );
+        assert_eq!(framer.payload(&data[..132]), 2..132);
+        assert_eq!(framer.payload(b"\x02ab"), 1..3);
+
+        let mut state = Default::default();
+        let overflow = [0xff; 10];
+        let err = framer.find_row_end(&mut state, &overflow).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn csv() {
+        let data = b"a,\"b\nc\"\r\n\"\"\"\n\",d\n\"\"\n";
+        assert_eq!(row_ends(&Csv, &[data]), [9, 17, 20]);
+        assert_eq!(
+            row_ends(&Csv, &[&data[..4], &data[4..12], &data[12..]]),
+            [9, 17, 20]
+        );
+        assert_eq!(Csv.payload(b"a,\"b\nc\"\r\n"), 0..7);
+        assert_eq!(Csv.payload(b"\"\"\n"), 0..2);
+        assert_eq!(Csv.payload(b"a"), 0..1);
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::{btree_map::Entry, BTreeMap};
+use std::sync::{Arc, RwLock};
+
+// Row checkpoints of a data source. An index can be shared by several cursors
+// over the same data through `Arc<RwLock<RowIndex>>`, so rows found by any of
+// them are known to all.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RowIndex {
+    pub(crate) granularity: u64,
+    // Byte offset of the first data row, after any header rows
+    pub(crate) origin: u64,
+    pub(crate) length: Option<u64>,
+    pub(crate) row_length: Option<u64>,
+    // Byte offset of every `granularity`-th row
+    pub(crate) checkpoints: BTreeMap<u64, u64>,
+    // Reverse mapping of `checkpoints` from byte offset to row
+    pub(crate) offsets: BTreeMap<u64, u64>,
+}
+
+impl RowIndex {
+    // Create index recording every `granularity`-th row
+    pub fn new(granularity: u64) -> Self {
+        Self {
+            granularity,
+            origin: 0,
+            length: None,
+            row_length: None,
+            checkpoints: BTreeMap::from([(0, 0)]),
+            offsets: BTreeMap::from([(0, 0)]),
+        }
+    }
+
+    // Create index ready to be shared between cursors
+    pub fn shared(granularity: u64) -> Arc<RwLock<Self>> {
+        Arc::new(RwLock::new(Self::new(granularity)))
+    }
+
+    pub fn granularity(&self) -> u64 {
+        self.granularity
+    }
+
+    // Return byte length of the data, once known
+    pub fn length(&self) -> Option<u64> {
+        self.length
+    }
+
+    // Return number of data rows, once known
+    pub fn row_length(&self) -> Option<u64> {
+        self.row_length
+    }
+
+    // Return byte offset of `row` if it is a checkpoint
+    pub fn get(&self, row: u64) -> Option<u64> {
+        self.checkpoints.get(&row).copied()
+    }
+
+    // Return checkpoints as (row, byte offset) pairs in row order
+    pub fn checkpoints(&self) -> impl DoubleEndedIterator<Item = (u64, u64)> + '_ {
+        self.checkpoints.iter().map(|(&row, &byte)| (row, byte))
+    }
+
+    // Add row checkpoint, keeping any existing checkpoint for the row
+    pub(crate) fn insert(&mut self, row: u64, byte: u64) {
+        if let Entry::Vacant(entry) = self.checkpoints.entry(row) {
+            entry.insert(byte);
+            self.offsets.insert(byte, row);
+        }
+    }
+
+    // Return the start of `row` for fixed-length rows of `len` bytes in data of
+    // `length` bytes, or the end of the data past the last row, as a
+    // (byte, row) pair
+    pub(crate) fn fixed_checkpoint(&self, row: u64, len: u64, length: u64) -> (u64, u64) {
+        let row = row.min((length - self.origin).div_ceil(len));
+        ((self.origin + row * len).min(length), row)
+    }
+
+    // Number the rows from the row at byte `origin`, which is row `skipped`.
+    // Checkpoints that no longer fall on a multiple of the granularity are
+    // dropped, so the index is the same as one built after the skipped rows.
+    pub(crate) fn rebase(&mut self, origin: u64, skipped: u64) {
+        let checkpoints = std::mem::take(&mut self.checkpoints);
+        self.offsets.clear();
+        self.insert(0, origin);
+        for (&row, &byte) in checkpoints.range(skipped..) {
+            if (row - skipped).is_multiple_of(self.granularity) {
+                self.insert(row - skipped, byte);
+            }
+        }
+        self.origin = origin;
+        self.row_length = self.row_length.map(|row_length| row_length - skipped);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RowIndex;
+
+    #[test]
+    fn rebase() {
+        let make_index = || {
+            let mut index = RowIndex::new(2);
+            for (row, byte) in [(2, 8), (4, 16), (6, 24)] {
+                index.insert(row, byte);
+            }
+            index.insert(2, 9);
+            index.row_length = Some(7);
+            index
+        };
+
+        let mut index = make_index();
+        index.rebase(8, 2);
+        assert_eq!(
+            index.checkpoints().collect::<Vec<_>>(),
+            [(0, 8), (2, 16), (4, 24)]
+        );
+        assert_eq!(index.offsets.len(), 3);
+        assert_eq!(index.get(2), Some(16));
+        assert_eq!(index.row_length(), Some(5));
+        assert_eq!(index.origin, 8);
+
+        // Checkpoints off the granularity are dropped
+        let mut index = make_index();
+        index.rebase(10, 3);
+        assert_eq!(index.checkpoints().collect::<Vec<_>>(), [(0, 10)]);
+        assert_eq!(index.offsets.len(), 1);
+        assert_eq!(index.row_length(), Some(4));
+    }
+}
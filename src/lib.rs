@@ -1,29 +1,176 @@
-use std::collections::BTreeMap;
 use std::io::{BufRead, Read, Seek, SeekFrom};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use source::Baseline;
+
+#[cfg(feature = "tokio")]
+mod async_cursor;
+mod column;
+mod follow;
+mod framer;
+mod index;
+mod multi;
+mod parallel;
+#[cfg(any(unix, windows))]
+mod positional;
+mod query;
+mod reverse;
+mod rows;
+mod separator;
+mod sidecar;
+mod source;
+#[cfg(test)]
+mod test_util;
+
+#[cfg(feature = "tokio")]
+pub use async_cursor::AsyncCachedRowCursor;
+pub use column::ColumnUnit;
+pub use framer::{Csv, FixedLength, LengthHeader, LengthPrefixed, LengthPrefixedState, RowFramer};
+pub use index::RowIndex;
+pub use multi::MultiRowCursor;
+#[cfg(any(unix, windows))]
+pub use positional::PositionalRowReader;
+pub use reverse::{RevRow, RowsRev};
+pub use rows::{Row, Rows};
+pub use separator::Separator;
+pub use source::{OnSourceChange, SourceChanged};
+
+// Cursor position saved by `mark`
+struct Mark<S> {
+    pos: u64,
+    row_pos: u64,
+    row_start: u64,
+    partial: bool,
+    state: S,
+}
 
-pub struct CachedRowCursor<T> {
+impl<S: Default> Mark<S> {
+    // Position at the start of row `row`, at byte `byte`
+    fn checkpoint(row: u64, byte: u64) -> Self {
+        Self {
+            pos: byte,
+            row_pos: row,
+            row_start: byte,
+            partial: false,
+            state: S::default(),
+        }
+    }
+}
+
+pub struct CachedRowCursor<T, F: RowFramer = Separator> {
     inner: T,
     pos: u64,
     row_pos: u64,
-    length: Option<u64>,
-    row_length: Option<u64>,
-    separator: u8,
+    // Byte offset of the start of the current row
+    row_start: u64,
+    // Whether bytes of the current row have been consumed
+    partial: bool,
+    framer: F,
+    // Framing state of the row at the current position
+    state: F::State,
+    // Whether `read_row` returns rows without framing bytes
+    strip_framing: bool,
+    // Granularity of `index`, which never changes
     granularity: u64,
-    cached_index: BTreeMap<u64, u64>,
+    // Rows before the first data row, excluded from row numbering
+    header: Vec<Vec<u8>>,
+    // Whether the data may grow, see `set_follow`
+    follow: bool,
+    // End of the rows known to be complete, up to which the `Read` and
+    // `BufRead` impls read in follow mode
+    complete_end: u64,
+    // Whether seeks check the data against the index, see `set_validation`
+    validation: Option<OnSourceChange>,
+    // Fingerprint of the indexed data when last validated
+    baseline: Option<Baseline>,
+    index: Arc<RwLock<RowIndex>>,
 }
 
 impl<T: BufRead + Seek> CachedRowCursor<T> {
-    pub fn new(reader: T, separator: u8, granularity: u64) -> Self {
-        Self {
-            inner: reader,
-            pos: 0,
-            row_pos: 0,
-            length: None,
-            row_length: None,
-            separator,
-            granularity,
-            cached_index: BTreeMap::from([(0, 0)]),
+    pub fn new<S: Into<Separator>>(reader: T, separator: S, granularity: u64) -> Self {
+        Self::with_framer(reader, separator.into(), granularity)
+    }
+}
+
+impl<T: BufRead + Seek> CachedRowCursor<T, LengthPrefixed> {
+    // Create cursor over rows prefixed by their length, where `read_row`
+    // returns only the payload
+    pub fn with_length_prefix(reader: T, header: LengthHeader, granularity: u64) -> Self {
+        let mut cursor = Self::with_framer(reader, LengthPrefixed::new(header), granularity);
+        cursor.strip_framing = true;
+        cursor
+    }
+}
+
+impl<T: BufRead + Seek, F: RowFramer> CachedRowCursor<T, F> {
+    // Create cursor with rows delimited by `framer`
+    pub fn with_framer(reader: T, framer: F, granularity: u64) -> Self {
+        Self::from_parts(reader, framer, RowIndex::shared(granularity))
+    }
+
+    // Create cursor sharing `index` with other cursors over the same data and
+    // using the same framing. The cursor starts at the first data row, but
+    // does not read header rows, which must be read before the index is shared.
+    pub fn with_index(
+        mut reader: T,
+        framer: F,
+        index: Arc<RwLock<RowIndex>>,
+    ) -> Result<Self, std::io::Error> {
+        let origin = index.read().unwrap_or_else(PoisonError::into_inner).origin;
+        reader.seek(SeekFrom::Current(origin as i64))?;
+        Ok(Self::from_parts(reader, framer, index))
+    }
+
+    // Return the row index, which can be shared with other cursors through `with_index`
+    pub fn index(&self) -> &Arc<RwLock<RowIndex>> {
+        &self.index
+    }
+
+    // Read the next `rows` rows as header rows, which are kept apart from the
+    // data so that row 0 is the first data row. Header rows are stripped of
+    // framing bytes if `set_strip_framing` is enabled.
+    //
+    // Header rows renumber the row index, so they cannot be read once the
+    // index already excludes header rows, or is shared and has rows found.
+    pub fn with_header_rows(mut self, rows: u64) -> Result<Self, std::io::Error> {
+        if self.lock_index().origin != 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "row index already excludes header rows",
+            ));
+        }
+        let has_rows = {
+            let index = self.lock_index();
+            index.length.is_some() || index.checkpoints().any(|(row, _)| row > 0)
+        };
+        if has_rows && Arc::strong_count(&self.index) > 1 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "cannot renumber rows of a shared row index",
+            ));
+        }
+        self.read_header_rows(rows)?;
+        Ok(self)
+    }
+
+    fn read_header_rows(&mut self, rows: u64) -> Result<(), std::io::Error> {
+        self.set_row_position(0)?;
+
+        let start = self.row_pos;
+        let mut buf = vec![];
+        while self.row_pos < start + rows && self.read_row(&mut buf)? != 0 {
+            self.header.push(std::mem::take(&mut buf));
         }
+        // Renumber the index from the first data row
+        self.lock_index_mut().rebase(self.pos, self.row_pos);
+        self.row_pos = 0;
+
+        Ok(())
+    }
+
+    // Return header rows read by `with_header_rows`
+    pub fn header(&self) -> &[Vec<u8>] {
+        &self.header
     }
 
     // Return current byte position
@@ -33,33 +180,11 @@ impl<T: BufRead + Seek> CachedRowCursor<T> {
 
     // Set byte position
     pub fn set_position(&mut self, pos: u64) -> Result<u64, std::io::Error> {
-        let (&cached_row, &cached_byte) = self
-            .cached_index
-            .iter()
-            .take_while(|(&_row, &byte)| byte < pos)
-            .last()
-            .unwrap_or((&0, &0));
-
-        self.pos = self
-            .inner
-            .seek(SeekFrom::Current(cached_byte as i64 - self.pos as i64))?;
-        self.row_pos = cached_row;
-
-        let mut byte_len;
-        let mut buf = vec![];
-        while self.pos < pos {
-            byte_len = self.read_row(&mut buf)?;
-            if byte_len == 0 {
-                break;
-            }
-        }
+        let fixed_length = self.fixed_length()?;
+        let (cached_byte, cached_row) = self.position_checkpoint(pos, fixed_length)?;
+        self.jump_to_checkpoint(cached_row, cached_byte)?;
 
-        if self.pos > pos {
-            self.inner
-                .seek(SeekFrom::Current(pos as i64 - self.pos as i64))?;
-            self.pos = pos;
-            self.row_pos -= 1;
-        }
+        while self.pos < pos && self.scan_row(None, pos - self.pos)? != 0 {}
 
         Ok(self.pos)
     }
@@ -69,54 +194,160 @@ impl<T: BufRead + Seek> CachedRowCursor<T> {
     }
 
     pub fn set_row_position(&mut self, row: u64) -> Result<u64, std::io::Error> {
-        // Get nearest cached position
-        let (&cached_row, &cached_byte) = self
-            .cached_index
-            .get_key_value(&(row / self.granularity))
-            .unwrap_or_else(|| self.cached_index.iter().last().unwrap_or((&0, &0)));
+        let fixed_length = self.fixed_length()?;
+        let (cached_byte, cached_row) = self.row_checkpoint(row, fixed_length);
+        self.jump_to_checkpoint(cached_row, cached_byte)?;
 
-        self.inner
-            .seek(SeekFrom::Current(cached_byte as i64 - self.pos as i64))?;
-        self.row_pos = cached_row;
-        self.pos = cached_byte;
+        while self.row_pos < row && self.scan_row(None, u64::MAX)? != 0 {}
 
-        let mut buf = vec![];
-        while self.row_pos < row && self.read_row(&mut buf).unwrap() != 0 {}
+        // In follow mode a partly written last row is left unread
+        if self.follow && self.partial {
+            self.jump_to_checkpoint(self.row_pos, self.row_start)?;
+        }
 
         Ok(self.row_pos)
     }
 
+    // Set whether `read_row` returns rows without framing bytes, such as separators
+    pub fn set_strip_framing(&mut self, strip: bool) {
+        self.strip_framing = strip;
+    }
+
+    // Append the rest of the current row to `buf`, returning the number of bytes
+    // consumed, including framing bytes even if they are stripped
     pub fn read_row(&mut self, buf: &mut Vec<u8>) -> Result<usize, std::io::Error> {
-        let byte_len = self.inner.read_until(self.separator, buf)?;
+        let start = buf.len();
+        let mark = self.follow.then(|| self.mark());
+        let byte_len = self.scan_row(Some(buf), u64::MAX)?;
+
+        // In follow mode a row is only read once it is complete
+        if let Some(mark) = mark.filter(|_| self.partial) {
+            buf.truncate(start);
+            self.restore(mark)?;
+            return Ok(0);
+        }
 
-        if byte_len != 0 {
-            self.pos += byte_len as u64;
-            self.row_pos += 1;
+        self.strip_row(buf, start);
+        Ok(byte_len)
+    }
 
-            if self.row_pos % self.granularity == 0
-                && !self.cached_index.contains_key(&self.row_pos)
-            {
-                self.cached_index.insert(self.row_pos, self.pos);
+    // Append row `row` to `buf` and return to the current position, returning
+    // the number of bytes in the row or 0 past the last row. The index is
+    // extended as rows are scanned.
+    pub fn get_row(&mut self, row: u64, buf: &mut Vec<u8>) -> Result<usize, std::io::Error> {
+        let mark = self.mark();
+        let result = match self.set_row_position(row) {
+            Ok(found) if found == row => self.read_row(buf),
+            Ok(_) => Ok(0),
+            Err(e) => Err(e),
+        };
+        self.restore(mark)?;
+        result
+    }
+
+    // Move to the start of a cached row
+    fn jump_to_checkpoint(&mut self, row: u64, byte: u64) -> Result<(), std::io::Error> {
+        self.restore(Mark::checkpoint(row, byte))
+    }
+
+    // Measure the data for fixed-length rows, returning its length, or `None`
+    // for other framers
+    fn fixed_length(&mut self) -> Result<Option<u64>, std::io::Error> {
+        let Some(len) = self.framer.fixed_len() else {
+            return Ok(None);
+        };
+        if let Some(length) = self.lock_index().length {
+            return Ok(Some(length));
+        }
+
+        let remaining = self.data_end()? - self.pos;
+        Ok(Some(self.record_fixed_length(len, remaining)))
+    }
+
+    // Scan all rows after the last cached position to complete the row index,
+    // then return to the current position. `progress` receives the bytes scanned
+    // and rows found so far, and cancels the scan by returning false.
+    //
+    // Returns the total number of rows, or `None` if the scan was cancelled.
+    pub fn build_index<P>(&mut self, mut progress: P) -> Result<Option<u64>, std::io::Error>
+    where
+        P: FnMut(u64, u64) -> bool,
+    {
+        if let Some(row_length) = self.lock_index().row_length {
+            return Ok(Some(row_length));
+        }
+        if self.fixed_length()?.is_some() {
+            return Ok(self.lock_index().row_length);
+        }
+
+        let mark = self.mark();
+        let (cached_row, cached_byte) = self
+            .lock_index()
+            .checkpoints()
+            .next_back()
+            .unwrap_or((0, 0));
+        self.jump_to_checkpoint(cached_row, cached_byte)?;
+
+        let mut cancelled = false;
+        while self.lock_index().row_length.is_none() {
+            if self.scan_row(None, u64::MAX)? != 0 && !progress(self.pos, self.row_pos) {
+                cancelled = true;
+                break;
             }
-        } else {
-            // Reached EOF
-            self.row_length = Some(self.row_pos);
-            self.length = Some(self.pos);
         }
 
-        Ok(byte_len)
+        self.restore(mark)?;
+
+        Ok(if cancelled {
+            None
+        } else {
+            self.lock_index().row_length
+        })
+    }
+
+    // Return the end of the data, leaving the reader in place
+    fn data_end(&mut self) -> Result<u64, std::io::Error> {
+        let current = self.inner.stream_position()?;
+        let end = self.inner.seek(SeekFrom::End(0))?;
+        self.inner.seek(SeekFrom::Start(current))?;
+        Ok(self.pos + end.saturating_sub(current))
+    }
+
+    // Append bytes from `start` to `end` to `buf`, then return the reader to
+    // the current position. Positions are relative to where the reader
+    // started, like the cursor position.
+    fn read_span(&mut self, start: u64, end: u64, buf: &mut Vec<u8>) -> Result<(), std::io::Error> {
+        self.inner
+            .seek(SeekFrom::Current(start as i64 - self.pos as i64))?;
+        let n = self.inner.by_ref().take(end - start).read_to_end(buf)?;
+        let reached = start + n as u64;
+        self.inner
+            .seek(SeekFrom::Current(self.pos as i64 - reached as i64))?;
+        Ok(())
+    }
+
+    // Return to a position saved by `mark`
+    fn restore(&mut self, mark: Mark<F::State>) -> Result<(), std::io::Error> {
+        self.inner
+            .seek(SeekFrom::Current(mark.pos as i64 - self.pos as i64))?;
+        self.set_mark(mark);
+        Ok(())
     }
 
     pub fn seek_row(&mut self, pos: SeekFrom) -> Result<u64, std::io::Error> {
+        if self.validation.is_some() {
+            self.validate()?;
+        }
+
         let pos = match pos {
             SeekFrom::Start(pos) => pos as i64,
             SeekFrom::Current(pos) => self.row_pos as i64 + pos,
             SeekFrom::End(pos) => {
-                let mut buf = vec![];
-                while self.row_length.is_none() {
-                    self.read_row(&mut buf)?;
+                if self.follow {
+                    self.refresh()?;
                 }
-                self.row_length.unwrap() as i64 - 1 + pos
+                let row_length = self.build_index(|_, _| true)?.unwrap();
+                row_length as i64 - 1 + pos
             }
         };
 
@@ -131,50 +362,397 @@ impl<T: BufRead + Seek> CachedRowCursor<T> {
     }
 }
 
-impl<T: Read> Read for CachedRowCursor<T> {
+impl<T: BufRead, F: RowFramer> CachedRowCursor<T, F> {
+    // Consume bytes up to the end of the current row, or at most `limit` bytes,
+    // searching for the row end in place. Consumed bytes are appended to `buf`
+    // if given. Returns the number of bytes consumed.
+    fn scan_row(
+        &mut self,
+        mut buf: Option<&mut Vec<u8>>,
+        limit: u64,
+    ) -> Result<usize, std::io::Error> {
+        let mut byte_len = 0;
+
+        while (byte_len as u64) < limit {
+            match self.step(buf.as_deref_mut(), limit - byte_len as u64)? {
+                Some((used, row_end)) => {
+                    byte_len += used;
+                    if row_end {
+                        return Ok(byte_len);
+                    }
+                }
+                None => {
+                    self.reach_eof()?;
+                    return Ok(byte_len);
+                }
+            }
+        }
+
+        // A row stopped at `limit` may already be complete, depending on what follows
+        if self.partial {
+            let available = self.inner.fill_buf()?;
+            let at_eof = available.is_empty();
+            let state = &mut self.state.clone();
+            let row_end = matches!(
+                frame(&self.framer, state, available, None, u64::MAX),
+                Ok((0, true))
+            );
+            self.stop_at_limit(at_eof, row_end)?;
+        }
+
+        Ok(byte_len)
+    }
+
+    // Consume buffered bytes up to the next row end, at most `limit` bytes.
+    // Returns the number of bytes consumed and whether they end a row, or
+    // `None` at EOF.
+    fn step(
+        &mut self,
+        buf: Option<&mut Vec<u8>>,
+        limit: u64,
+    ) -> Result<Option<(usize, bool)>, std::io::Error> {
+        let (used, row_end) = loop {
+            let available = match self.inner.fill_buf() {
+                Ok(available) => available,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if available.is_empty() {
+                return Ok(None);
+            }
+            break frame(&self.framer, &mut self.state, available, buf, limit)?;
+        };
+
+        self.inner.consume(used);
+        self.consumed(used, row_end);
+        Ok(Some((used, row_end)))
+    }
+}
+
+impl<T, F: RowFramer> CachedRowCursor<T, F> {
+    // Create cursor at the first data row of `index`, which `reader` must be at
+    fn from_parts(reader: T, framer: F, index: Arc<RwLock<RowIndex>>) -> Self {
+        let (granularity, origin) = {
+            let index = index.read().unwrap_or_else(PoisonError::into_inner);
+            (index.granularity, index.origin)
+        };
+        Self {
+            inner: reader,
+            pos: origin,
+            row_pos: 0,
+            row_start: origin,
+            partial: false,
+            framer,
+            state: F::State::default(),
+            strip_framing: false,
+            granularity,
+            header: vec![],
+            follow: false,
+            complete_end: 0,
+            validation: None,
+            baseline: None,
+            index,
+        }
+    }
+
+    // Save the current position, including the framing state within a row
+    fn mark(&self) -> Mark<F::State> {
+        Mark {
+            pos: self.pos,
+            row_pos: self.row_pos,
+            row_start: self.row_start,
+            partial: self.partial,
+            state: self.state.clone(),
+        }
+    }
+
+    // Return to a position saved by `mark` once the reader is there
+    fn set_mark(&mut self, mark: Mark<F::State>) {
+        self.pos = mark.pos;
+        self.row_pos = mark.row_pos;
+        self.row_start = mark.row_start;
+        self.partial = mark.partial;
+        self.state = mark.state;
+    }
+
+    // Return the cached position to scan from to reach byte `pos`, as a
+    // (byte, row) pair. `fixed_length` is the data length measured for
+    // fixed-length rows.
+    fn position_checkpoint(
+        &self,
+        pos: u64,
+        fixed_length: Option<u64>,
+    ) -> Result<(u64, u64), std::io::Error> {
+        let index = self.lock_index();
+        if pos < index.origin {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek into the header rows",
+            ));
+        }
+
+        Ok(match self.framer.fixed_len().zip(fixed_length) {
+            Some((len, length)) => index.fixed_checkpoint((pos - index.origin) / len, len, length),
+            None => index
+                .offsets
+                .range(..pos)
+                .next_back()
+                .map_or((index.origin, 0), |(&byte, &row)| (byte, row)),
+        })
+    }
+
+    // Return the nearest cached position at or before row `row`, as a
+    // (byte, row) pair. `fixed_length` is as for `position_checkpoint`.
+    fn row_checkpoint(&self, row: u64, fixed_length: Option<u64>) -> (u64, u64) {
+        let index = self.lock_index();
+        match self.framer.fixed_len().zip(fixed_length) {
+            Some((len, length)) => index.fixed_checkpoint(row, len, length),
+            None => index
+                .checkpoints
+                .range(..=row)
+                .next_back()
+                .map_or((index.origin, 0), |(&row, &byte)| (byte, row)),
+        }
+    }
+
+    // Record the length of the data for fixed-length rows of `len` bytes,
+    // given the bytes left after the current position. This gives the row
+    // count without reading any rows. Returns the length.
+    fn record_fixed_length(&mut self, len: u64, remaining: u64) -> u64 {
+        let mut length = self.pos + remaining;
+        let mut index = self.lock_index_mut();
+        let data_len = length.saturating_sub(index.origin);
+        let rows = if self.follow {
+            // A partly written last row is not counted
+            length -= data_len % len;
+            data_len / len
+        } else {
+            data_len.div_ceil(len)
+        };
+        index.length = Some(length);
+        index.row_length = Some(rows);
+        length
+    }
+
+    // Update positions and index for `used` bytes consumed as found by `frame`
+    fn consumed(&mut self, used: usize, row_end: bool) {
+        self.pos += used as u64;
+        if row_end {
+            self.end_row();
+        } else {
+            self.partial = true;
+        }
+    }
+
+    // Settle a row stopped at a byte limit, given whether the data ends there
+    // and whether a row end follows
+    fn stop_at_limit(&mut self, at_eof: bool, row_end: bool) -> Result<(), std::io::Error> {
+        if at_eof {
+            self.reach_eof()?;
+        } else if row_end {
+            self.state = F::State::default();
+            self.end_row();
+        }
+        Ok(())
+    }
+
+    // Remove framing bytes from the row appended to `buf` at `start`, if enabled
+    fn strip_row(&self, buf: &mut Vec<u8>, start: usize) {
+        if self.strip_framing {
+            let payload = self.framer.payload(&buf[start..]);
+            buf.copy_within(start + payload.start..start + payload.end, start);
+            buf.truncate(start + payload.len());
+        }
+    }
+
+    // Update positions and index for bytes consumed from the reader
+    fn advance(&mut self, bytes: &[u8]) -> Result<(), std::io::Error> {
+        let start = self.pos;
+        let mut row_start = 0;
+        let result = loop {
+            match self
+                .framer
+                .find_row_end(&mut self.state, &bytes[row_start..])
+            {
+                Ok(Some(i)) => {
+                    row_start += i;
+                    self.pos = start + row_start as u64;
+                    self.end_row();
+                }
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+
+        if row_start < bytes.len() {
+            self.partial = true;
+        }
+        self.pos = start + bytes.len() as u64;
+        result
+    }
+
+    // Count a row ending at the current position
+    fn end_row(&mut self) {
+        self.row_pos += 1;
+        self.row_start = self.pos;
+        self.partial = false;
+        self.complete_end = self.complete_end.max(self.pos);
+
+        // Fixed-length rows are located without an index
+        if self.framer.fixed_len().is_none() && self.row_pos.is_multiple_of(self.granularity) {
+            self.insert_checkpoint(self.row_pos, self.pos);
+        }
+    }
+
+    // Record that the input ends at the current position
+    fn reach_eof(&mut self) -> Result<(), std::io::Error> {
+        // In follow mode a last row without a trailing row end may still be
+        // written, so the data is taken to end before it
+        if self.partial && self.follow {
+            let mut index = self.lock_index_mut();
+            index.row_length = Some(self.row_pos);
+            index.length = Some(self.row_start);
+            return Ok(());
+        }
+
+        // A last row without a trailing row end ends at EOF
+        if self.partial {
+            self.framer.finish(&self.state)?;
+            self.state = F::State::default();
+            self.end_row();
+        }
+
+        let mut index = self.lock_index_mut();
+        index.row_length = Some(self.row_pos);
+        index.length = Some(self.pos);
+        Ok(())
+    }
+
+    // Add row checkpoint to the index
+    fn insert_checkpoint(&mut self, row: u64, byte: u64) {
+        self.lock_index_mut().insert(row, byte);
+    }
+
+    // Lock the index for reading. Poisoning is ignored, as every update leaves
+    // the index consistent.
+    fn lock_index(&self) -> RwLockReadGuard<'_, RowIndex> {
+        self.index.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Lock the index for writing
+    fn lock_index_mut(&self) -> RwLockWriteGuard<'_, RowIndex> {
+        self.index.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// Find the next row end in buffered bytes `available`, looking at most
+// `limit` bytes ahead, and append the bytes up to it to `buf` if given.
+// Returns the number of bytes to consume and whether they end a row, to be
+// passed to `consumed` once consumed.
+fn frame<F: RowFramer>(
+    framer: &F,
+    state: &mut F::State,
+    available: &[u8],
+    buf: Option<&mut Vec<u8>>,
+    limit: u64,
+) -> Result<(usize, bool), std::io::Error> {
+    let available = &available[..limit.min(available.len() as u64) as usize];
+    let (used, row_end) = match framer.find_row_end(state, available)? {
+        Some(i) => (i, true),
+        None => (available.len(), false),
+    };
+    if let Some(buf) = buf {
+        buf.extend_from_slice(&available[..used]);
+    }
+    Ok((used, row_end))
+}
+
+impl<T: BufRead + Seek, F: RowFramer> Read for CachedRowCursor<T, F> {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        if self.follow {
+            let available = self.fill_buf()?;
+            let n = available.len().min(buf.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            self.consume(n);
+            return Ok(n);
+        }
+
         let n = self.inner.read(buf)?;
-        self.pos += n as u64;
-        self.row_pos += buf.iter().filter(|&&s| s == self.separator).count() as u64;
+        if n == 0 && !buf.is_empty() {
+            self.reach_eof()?;
+        } else {
+            self.advance(&buf[..n])?;
+        }
         Ok(n)
     }
 }
 
-impl<T: BufRead> BufRead for CachedRowCursor<T> {
+impl<T: BufRead + Seek, F: RowFramer> BufRead for CachedRowCursor<T, F> {
     fn fill_buf(&mut self) -> Result<&[u8], std::io::Error> {
+        if self.follow {
+            return self.fill_complete_rows();
+        }
+        if self.inner.fill_buf()?.is_empty() {
+            self.reach_eof()?;
+        }
         self.inner.fill_buf()
     }
 
-    fn consume(&mut self, amt: usize) {
-        self.pos += amt as u64;
-        self.inner.consume(amt)
+    fn consume(&mut self, mut amt: usize) {
+        // Consume row by row so that checkpoints land on row ends
+        while amt > 0 {
+            match self.step(None, amt as u64) {
+                Ok(Some((used, _))) => amt -= used,
+                _ => {
+                    self.inner.consume(amt);
+                    self.pos += amt as u64;
+                    break;
+                }
+            }
+        }
     }
 
     fn read_until(&mut self, byte: u8, buf: &mut Vec<u8>) -> Result<usize, std::io::Error> {
+        let start = buf.len();
+        if self.follow {
+            loop {
+                let available = self.fill_buf()?;
+                let (used, done) = match memchr::memchr(byte, available) {
+                    Some(i) => (i + 1, true),
+                    None => (available.len(), available.is_empty()),
+                };
+                buf.extend_from_slice(&available[..used]);
+                self.consume(used);
+                if done {
+                    return Ok(buf.len() - start);
+                }
+            }
+        }
+
         let result = self.inner.read_until(byte, buf);
-        self.row_pos += if byte != self.separator {
-            buf.iter()
-                .take_while(|&&s| s != byte)
-                .filter(|&&s| s == self.separator)
-                .count() as u64
-        } else {
-            1
-        };
+        self.advance(&buf[start..])?;
+        if let Ok(0) = result {
+            self.reach_eof()?;
+        }
         result
     }
 }
 
-impl<T: BufRead + Seek> Seek for CachedRowCursor<T> {
+impl<T: BufRead + Seek, F: RowFramer> Seek for CachedRowCursor<T, F> {
     fn seek(&mut self, pos: SeekFrom) -> Result<u64, std::io::Error> {
+        if self.validation.is_some() {
+            self.validate()?;
+        }
+
         let pos = match pos {
             SeekFrom::Start(n) => n as i64,
             SeekFrom::Current(n) => self.pos as i64 + n,
             SeekFrom::End(n) => {
-                let mut buf = vec![];
-                while self.length.is_none() {
-                    self.read_row(&mut buf)?;
+                if self.follow {
+                    self.refresh()?;
                 }
-                self.length.unwrap() as i64 - 1 + n
+                self.build_index(|_, _| true)?;
+                self.lock_index().length.unwrap() as i64 - 1 + n
             }
         };
 
@@ -195,12 +773,21 @@ impl<T: BufRead + Seek> Seek for CachedRowCursor<T> {
 
 #[cfg(test)]
 mod tests {
-    use super::CachedRowCursor;
-    use std::io::{BufReader, Cursor, Seek, SeekFrom};
+    use super::{
+        CachedRowCursor, Csv, FixedLength, LengthHeader, LengthPrefixed, RowIndex, Separator,
+    };
+    use crate::test_util::Counting;
+    use std::io::{BufRead, BufReader, Cursor, ErrorKind, Read, Seek, SeekFrom};
 
     fn make_cursor() -> CachedRowCursor<BufReader<Cursor<&'static [u8; 20]>>> {
+        make_cursor_with_granularity(1)
+    }
+
+    fn make_cursor_with_granularity(
+        granularity: u64,
+    ) -> CachedRowCursor<BufReader<Cursor<&'static [u8; 20]>>> {
         let data = BufReader::new(Cursor::new(b"foo\nbar\nbiz\nbaz\nbuz\n"));
-        CachedRowCursor::new(data, b'\n', 1)
+        CachedRowCursor::new(data, b'\n', granularity)
     }
 
     #[test]
@@ -267,7 +854,7 @@ mod tests {
 
         assert!(cursor.seek(SeekFrom::End(-20)).is_err());
 
-        assert_eq!(cursor.length.unwrap(), 20);
+        assert_eq!(cursor.lock_index().length.unwrap(), 20);
     }
 
     #[test]
@@ -308,7 +895,7 @@ mod tests {
 
         assert!(cursor.seek_row(SeekFrom::End(-5)).is_err());
 
-        assert_eq!(cursor.row_length.unwrap(), 5);
+        assert_eq!(cursor.lock_index().row_length.unwrap(), 5);
     }
 
     #[test]
@@ -336,10 +923,300 @@ mod tests {
         assert_eq!(cursor.row_position(), 5);
     }
 
+    #[test]
+    fn build_index() {
+        let mut cursor = make_cursor();
+        assert_eq!(cursor.set_row_position(2).unwrap(), 2);
+
+        let mut reports = vec![];
+        let rows = cursor.build_index(|bytes, rows| {
+            reports.push((bytes, rows));
+            true
+        });
+        assert_eq!(rows.unwrap(), Some(5));
+        assert_eq!(reports, [(12, 3), (16, 4), (20, 5)]);
+        assert_eq!(cursor.lock_index().checkpoints.len(), 6);
+        assert_eq!(cursor.lock_index().length, Some(20));
+        assert_eq!(cursor.lock_index().row_length, Some(5));
+        assert_eq!(cursor.position(), 8);
+        assert_eq!(cursor.row_position(), 2);
+
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 4);
+        assert_eq!(buf, b"biz\n");
+    }
+
+    #[test]
+    fn build_index_cancelled() {
+        let mut cursor = make_cursor();
+
+        let rows = cursor.build_index(|_, rows| rows < 2);
+        assert_eq!(rows.unwrap(), None);
+        assert_eq!(cursor.lock_index().checkpoints.len(), 3);
+        assert_eq!(cursor.lock_index().row_length, None);
+        assert_eq!(cursor.position(), 0);
+        assert_eq!(cursor.row_position(), 0);
+
+        assert_eq!(cursor.build_index(|_, _| true).unwrap(), Some(5));
+        assert_eq!(cursor.lock_index().checkpoints.len(), 6);
+    }
+
+    #[test]
+    fn seek_updates_index() {
+        for granularity in 1..=6 {
+            let mut expected = make_cursor_with_granularity(granularity);
+            let mut buf = vec![];
+            while expected.read_row(&mut buf).unwrap() != 0 {}
+
+            let mut cursor = make_cursor_with_granularity(granularity);
+            assert_eq!(cursor.set_row_position(5).unwrap(), 5);
+            assert_eq!(cursor.position(), 20);
+            assert_eq!(
+                cursor.lock_index().checkpoints,
+                expected.lock_index().checkpoints
+            );
+
+            let mut cursor = make_cursor_with_granularity(granularity);
+            assert_eq!(cursor.set_position(19).unwrap(), 19);
+            assert_eq!(cursor.row_position(), 4);
+            assert_eq!(cursor.seek(SeekFrom::End(0)).unwrap(), 19);
+            assert_eq!(
+                cursor.lock_index().checkpoints,
+                expected.lock_index().checkpoints
+            );
+        }
+    }
+
+    // Naive model of the rows in `data`, as (start, end) byte ranges
+    fn model_rows(data: &[u8], separator: &Separator) -> Vec<(u64, u64)> {
+        let mut rows = vec![];
+        let mut start = 0;
+        let mut i = 0;
+        while i < data.len() {
+            let separator_len = match separator.as_bytes() {
+                Some(bytes) if data[i..].starts_with(bytes) => bytes.len(),
+                Some(_) => 0,
+                None if data[i..].starts_with(b"\r\n") => 2,
+                None if data[i] == b'\r' || data[i] == b'\n' => 1,
+                None => 0,
+            };
+            if separator_len != 0 {
+                i += separator_len;
+                rows.push((start as u64, i as u64));
+                start = i;
+            } else {
+                i += 1;
+            }
+        }
+        if start < data.len() {
+            rows.push((start as u64, data.len() as u64));
+        }
+        rows
+    }
+
+    fn separators() -> [Separator; 5] {
+        [
+            Separator::from(b'\n'),
+            Separator::from(b"\r\n"),
+            Separator::from(b"||\n"),
+            Separator::from(b"aa"),
+            Separator::universal_newline(),
+        ]
+    }
+
+    // Deterministic pseudo-random generator for differential tests
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, bound: u64) -> u64 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 33) % bound
+        }
+    }
+
+    fn random_data(rng: &mut Lcg) -> Vec<u8> {
+        let len = rng.next(200);
+        (0..len)
+            .map(|_| match rng.next(8) {
+                0 | 1 => b'\n',
+                2 => b'\r',
+                3 => b'|',
+                4 => b'b',
+                _ => b'a',
+            })
+            .collect()
+    }
+
+    #[test]
+    fn differential() {
+        let mut rng = Lcg(0x5eed);
+
+        for (case, separator) in (0..500).zip(separators().iter().cycle()) {
+            let data = random_data(&mut rng);
+            let rows = model_rows(&data, separator);
+            let len = data.len() as u64;
+            let row_len = rows.len() as u64;
+            let row_start = |row: u64| rows.get(row as usize).map_or(len, |r| r.0);
+            let row_at = |byte: u64| rows.iter().filter(|r| r.1 <= byte).count() as u64;
+
+            for granularity in [1, 2, 3, 4, 5, 7, 8, 10, 16, 100] {
+                let mut cursor = CachedRowCursor::new(
+                    BufReader::with_capacity(16, Cursor::new(data.clone())),
+                    separator.clone(),
+                    granularity,
+                );
+
+                for _ in 0..50 {
+                    let context = format!("case {case}, granularity {granularity}");
+                    match rng.next(5) {
+                        0 => {
+                            let row = rng.next(row_len + 3);
+                            let expected = row.min(row_len);
+                            assert_eq!(
+                                cursor.set_row_position(row).unwrap(),
+                                expected,
+                                "{context}"
+                            );
+                            assert_eq!(cursor.position(), row_start(expected), "{context}");
+                        }
+                        1 => {
+                            let byte = rng.next(len + 3);
+                            let expected = byte.min(len);
+                            assert_eq!(cursor.set_position(byte).unwrap(), expected, "{context}");
+                            assert_eq!(cursor.row_position(), row_at(expected), "{context}");
+                        }
+                        2 => {
+                            let offset = rng.next(row_len + 3) as i64 - row_len as i64 - 1;
+                            let target = row_len as i64 - 1 + offset;
+                            let result = cursor.seek_row(SeekFrom::End(offset));
+                            if target < 0 {
+                                assert!(result.is_err(), "{context}");
+                            } else {
+                                assert_eq!(result.unwrap(), target as u64, "{context}");
+                                assert_eq!(
+                                    cursor.position(),
+                                    row_start(target as u64),
+                                    "{context}"
+                                );
+                            }
+                        }
+                        _ => {
+                            let pos = cursor.position();
+                            let row = cursor.row_position();
+                            let mut buf = vec![];
+                            let n = cursor.read_row(&mut buf).unwrap();
+                            let end = rows.iter().find(|r| r.1 > pos).map_or(len, |r| r.1);
+                            assert_eq!(buf, &data[pos as usize..end as usize], "{context}");
+                            assert_eq!(n as u64, end - pos, "{context}");
+                            assert_eq!(
+                                cursor.row_position(),
+                                if n == 0 { row } else { row + 1 },
+                                "{context}"
+                            );
+                        }
+                    }
+
+                    for (&row, &byte) in &cursor.lock_index().checkpoints {
+                        assert!(row.is_multiple_of(granularity), "{context}");
+                        assert_eq!(byte, row_start(row), "{context}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mixed_access() {
+        let mut rng = Lcg(0xacce55);
+
+        for (case, separator) in (0..500).zip(separators().iter().cycle()) {
+            let data = random_data(&mut rng);
+            let rows = model_rows(&data, separator);
+            let len = data.len() as u64;
+            let row_start = |row: u64| rows.get(row as usize).map_or(len, |r| r.0);
+            let row_at = |byte: u64| rows.iter().filter(|r| r.1 <= byte).count() as u64;
+
+            for granularity in [1, 2, 3, 5] {
+                let context = format!("case {case}, granularity {granularity}");
+                let mut cursor = CachedRowCursor::new(
+                    BufReader::with_capacity(7, Cursor::new(data.clone())),
+                    separator.clone(),
+                    granularity,
+                );
+
+                let mut read = vec![];
+                while cursor.lock_index().length.is_none() {
+                    let mut buf = vec![];
+                    match rng.next(6) {
+                        0 => {
+                            let mut chunk = vec![0; rng.next(10) as usize + 1];
+                            let n = cursor.read(&mut chunk).unwrap();
+                            buf.extend_from_slice(&chunk[..n]);
+                        }
+                        1 => {
+                            let available = cursor.fill_buf().unwrap().to_vec();
+                            let amt = rng.next(available.len() as u64 + 1) as usize;
+                            buf.extend_from_slice(&available[..amt]);
+                            cursor.consume(amt);
+                        }
+                        2 => {
+                            cursor.read_until(b'a', &mut buf).unwrap();
+                        }
+                        3 => {
+                            cursor.read_until(b'\n', &mut buf).unwrap();
+                        }
+                        4 => {
+                            let mut line = String::new();
+                            cursor.read_line(&mut line).unwrap();
+                            buf.extend_from_slice(line.as_bytes());
+                        }
+                        _ => {
+                            cursor.read_row(&mut buf).unwrap();
+                        }
+                    }
+                    read.extend_from_slice(&buf);
+
+                    // A lone `\r` only ends a row once the following byte is read
+                    let pending_newline =
+                        separator.as_bytes().is_none() && read.last() == Some(&b'\r');
+
+                    assert_eq!(cursor.position(), read.len() as u64, "{context}");
+                    if cursor.position() < len && !pending_newline {
+                        assert_eq!(
+                            cursor.row_position(),
+                            row_at(cursor.position()),
+                            "{context}"
+                        );
+                    }
+                }
+
+                assert_eq!(read, data, "{context}");
+                assert_eq!(cursor.row_position(), rows.len() as u64, "{context}");
+                assert_eq!(
+                    cursor.lock_index().row_length,
+                    Some(rows.len() as u64),
+                    "{context}"
+                );
+                assert_eq!(cursor.lock_index().length, Some(len), "{context}");
+                assert_eq!(
+                    cursor.lock_index().checkpoints.len() as u64,
+                    rows.len() as u64 / granularity + 1,
+                    "{context}"
+                );
+                for (&row, &byte) in &cursor.lock_index().checkpoints {
+                    assert_eq!(byte, row_start(row), "{context}");
+                }
+            }
+        }
+    }
+
     #[test]
     fn separator() {
         let mut cursor = make_cursor();
-        cursor.separator = b'a';
+        cursor.framer = Separator::from(b'a');
 
         assert_eq!(cursor.seek(SeekFrom::End(0)).unwrap(), 19);
         assert_eq!(cursor.row_position(), 2);
@@ -353,11 +1230,368 @@ mod tests {
     }
 
     #[test]
-    fn granularity() {
+    fn multi_byte_separator() {
+        let data = BufReader::with_capacity(3, Cursor::new(b"foo\r\nbar\r\nb\rz\r\nbuz"));
+        let mut cursor = CachedRowCursor::new(data, b"\r\n", 2);
+
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 5);
+        assert_eq!(buf, b"foo\r\n");
+
+        assert_eq!(cursor.seek_row(SeekFrom::End(-1)).unwrap(), 2);
+        assert_eq!(cursor.position(), 10);
+        assert_eq!(cursor.lock_index().row_length, Some(4));
+
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 5);
+        assert_eq!(buf, b"b\rz\r\n");
+        assert_eq!(cursor.row_position(), 3);
+
+        assert_eq!(cursor.set_position(14).unwrap(), 14);
+        assert_eq!(cursor.row_position(), 2);
+        assert_eq!(cursor.set_position(15).unwrap(), 15);
+        assert_eq!(cursor.row_position(), 3);
+        assert_eq!(
+            cursor.lock_index().checkpoints.keys().collect::<Vec<_>>(),
+            [&0, &2, &4]
+        );
+    }
+
+    #[test]
+    fn universal_newline() {
+        let data = BufReader::with_capacity(4, Cursor::new(b"foo\r\nbar\rbiz\nb\rz\r\r\nbuz"));
+        let mut cursor = CachedRowCursor::new(data, Separator::universal_newline(), 1);
+        cursor.set_strip_framing(true);
+
+        let mut rows = vec![];
+        let mut buf = vec![];
+        while cursor.read_row(&mut buf).unwrap() != 0 {
+            rows.push(String::from_utf8(buf.clone()).unwrap());
+            buf.clear();
+        }
+        assert_eq!(rows, ["foo", "bar", "biz", "b", "z", "", "buz"]);
+        assert_eq!(cursor.lock_index().row_length, Some(7));
+
+        assert_eq!(cursor.seek_row(SeekFrom::Start(4)).unwrap(), 4);
+        assert_eq!(cursor.position(), 15);
+        assert_eq!(cursor.set_position(14).unwrap(), 14);
+        assert_eq!(cursor.row_position(), 3);
+        assert_eq!(cursor.set_position(17).unwrap(), 17);
+        assert_eq!(cursor.row_position(), 5);
+        assert_eq!(cursor.set_position(18).unwrap(), 18);
+        assert_eq!(cursor.row_position(), 5);
+
+        cursor.set_strip_framing(false);
+        assert_eq!(cursor.seek_row(SeekFrom::Start(0)).unwrap(), 0);
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 5);
+        assert_eq!(buf, b"foo\r\n");
+    }
+
+    #[test]
+    fn fixed_length() {
+        let data = BufReader::with_capacity(2, Cursor::new(b"foobarbizba"));
+        let mut cursor = CachedRowCursor::with_framer(data, FixedLength::new(3), 2);
+
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 3);
+        assert_eq!(buf, b"foo");
+
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 3);
+        assert_eq!(cursor.position(), 9);
+        assert_eq!(cursor.lock_index().row_length, Some(4));
+
+        assert_eq!(cursor.set_position(5).unwrap(), 5);
+        assert_eq!(cursor.row_position(), 1);
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 1);
+        assert_eq!(buf, b"r");
+        assert_eq!(cursor.row_position(), 2);
+        assert_eq!(cursor.lock_index().checkpoints.len(), 1);
+    }
+
+    #[test]
+    fn fixed_length_without_reading() {
+        let data = b"0123456\n".repeat(100_000);
+        let reader = BufReader::with_capacity(8, Counting::new(data));
+        let mut cursor = CachedRowCursor::with_framer(reader, FixedLength::new(8), 1);
+
+        assert_eq!(cursor.build_index(|_, _| true).unwrap(), Some(100_000));
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 99_999);
+        assert_eq!(cursor.position(), 799_992);
+        assert_eq!(cursor.seek_row(SeekFrom::Start(200_000)).unwrap(), 100_000);
+        assert_eq!(cursor.position(), 800_000);
+        assert_eq!(cursor.seek(SeekFrom::Start(8 * 12_345)).unwrap(), 98_760);
+        assert_eq!(cursor.row_position(), 12_345);
+        assert_eq!(cursor.inner.get_ref().read, 0);
+
+        assert_eq!(cursor.set_position(8 * 500 + 3).unwrap(), 4_003);
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 5);
+        assert_eq!(buf, b"3456\n");
+        assert_eq!(cursor.row_position(), 501);
+        assert!(cursor.inner.get_ref().read <= 16);
+        assert_eq!(cursor.lock_index().checkpoints.len(), 1);
+    }
+
+    #[test]
+    fn length_prefixed() {
+        let data = BufReader::with_capacity(3, Cursor::new(b"\x03\0\0\0foo\0\0\0\0\x02\0\0\0ba"));
+        let mut cursor = CachedRowCursor::with_framer(data, LengthPrefixed::default(), 1);
+        cursor.set_strip_framing(true);
+
+        let mut rows = vec![];
+        let mut buf = vec![];
+        while cursor.read_row(&mut buf).unwrap() != 0 {
+            rows.push(buf.clone());
+            buf.clear();
+        }
+        assert_eq!(rows, [&b"foo"[..], b"", b"ba"]);
+        assert_eq!(cursor.lock_index().row_length, Some(3));
+
+        assert_eq!(cursor.seek_row(SeekFrom::Start(1)).unwrap(), 1);
+        assert_eq!(cursor.position(), 7);
+        assert_eq!(cursor.set_position(12).unwrap(), 12);
+        assert_eq!(cursor.row_position(), 2);
+
+        cursor.set_strip_framing(false);
+        assert_eq!(cursor.seek_row(SeekFrom::Start(2)).unwrap(), 2);
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 6);
+        assert_eq!(buf, b"\x02\0\0\0ba");
+    }
+
+    #[test]
+    fn varint_length_prefix() {
+        let mut data = vec![];
+        for len in [3, 0, 200, 1] {
+            let mut n = len;
+            while n >= 0x80 {
+                data.push(n as u8 | 0x80);
+                n >>= 7;
+            }
+            data.push(n as u8);
+            data.extend(std::iter::repeat_n(b'a' + len as u8 % 26, len));
+        }
+
+        let reader = BufReader::with_capacity(5, Cursor::new(data.clone()));
+        let mut cursor = CachedRowCursor::with_length_prefix(reader, LengthHeader::Varint, 2);
+
+        assert_eq!(cursor.seek_row(SeekFrom::Start(2)).unwrap(), 2);
+        assert_eq!(cursor.position(), 5);
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 202);
+        assert_eq!(buf, [b's'; 200]);
+
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 3);
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 2);
+        assert_eq!(buf, b"b");
+        assert_eq!(cursor.lock_index().row_length, Some(4));
+        assert_eq!(
+            cursor.lock_index().checkpoints.keys().collect::<Vec<_>>(),
+            [&0, &2, &4]
+        );
+    }
+
+    #[test]
+    fn length_prefix_past_eof() {
+        let data = BufReader::with_capacity(3, Cursor::new(b"\x02\0ab\x09\0abc"));
+        let mut cursor = CachedRowCursor::with_length_prefix(data, LengthHeader::U16Le, 1);
+
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 4);
+        assert_eq!(buf, b"ab");
+
+        let err = cursor.read_row(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        assert_eq!(cursor.row_position(), 1);
+        let err = cursor.seek_row(SeekFrom::End(0)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        assert_eq!(cursor.lock_index().row_length, None);
+    }
+
+    #[test]
+    fn csv() {
+        let data = b"id,note\n1,\"a\nb\"\n2,c\n3,\"d\r\ne\"\r\n4,f";
+        let mut cursor =
+            CachedRowCursor::with_framer(BufReader::with_capacity(3, Cursor::new(data)), Csv, 1);
+
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 4);
+        assert_eq!(cursor.lock_index().row_length, Some(5));
+        assert_eq!(
+            cursor.lock_index().checkpoints.values().collect::<Vec<_>>(),
+            [&0, &8, &16, &20, &30, &33]
+        );
+
+        cursor.set_strip_framing(true);
+        assert_eq!(cursor.seek_row(SeekFrom::Start(3)).unwrap(), 3);
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 10);
+        assert_eq!(buf, b"3,\"d\r\ne\"");
+
+        assert_eq!(cursor.set_position(12).unwrap(), 12);
+        assert_eq!(cursor.row_position(), 1);
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 4);
+        assert_eq!(buf, b"\nb\"");
+        assert_eq!(cursor.row_position(), 2);
+    }
+
+    #[test]
+    fn header_rows() {
+        let data = BufReader::with_capacity(
+            3,
+            Cursor::new(b"id,name\r\nunit,-\r\n1,foo\r\n2,bar\r\n3,biz"),
+        );
+        let mut cursor = CachedRowCursor::new(data, b"\r\n", 1);
+        cursor.set_strip_framing(true);
+        let mut cursor = cursor.with_header_rows(2).unwrap();
+
+        assert_eq!(cursor.header(), [&b"id,name"[..], b"unit,-"]);
+        assert_eq!(cursor.position(), 17);
+        assert_eq!(cursor.row_position(), 0);
+        assert_eq!(
+            cursor.lock_index().checkpoints.iter().collect::<Vec<_>>(),
+            [(&0, &17)]
+        );
+
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 7);
+        assert_eq!(buf, b"1,foo");
+
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 2);
+        assert_eq!(cursor.position(), 31);
+        assert_eq!(cursor.lock_index().row_length, Some(3));
+        assert_eq!(
+            cursor.lock_index().checkpoints.iter().collect::<Vec<_>>(),
+            [(&0, &17), (&1, &24), (&2, &31), (&3, &36)]
+        );
+
+        assert_eq!(cursor.set_position(25).unwrap(), 25);
+        assert_eq!(cursor.row_position(), 1);
+        assert_eq!(cursor.set_position(17).unwrap(), 17);
+        assert_eq!(cursor.row_position(), 0);
+        let err = cursor.set_position(16).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        let err = cursor.seek(SeekFrom::Start(0)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn header_rows_after_index() {
+        let mut cursor = make_cursor_with_granularity(2);
+        cursor.build_index(|_, _| true).unwrap();
+        let mut cursor = cursor.with_header_rows(1).unwrap();
+
+        assert_eq!(cursor.header(), [b"foo\n"]);
+        assert_eq!(cursor.lock_index().row_length, Some(4));
+        assert_eq!(
+            cursor.lock_index().checkpoints.iter().collect::<Vec<_>>(),
+            [(&0, &4)]
+        );
+        assert_eq!(cursor.seek_row(SeekFrom::Start(2)).unwrap(), 2);
+        assert_eq!(cursor.position(), 12);
+
+        // Rows scanned again give the index built after the header rows
+        assert_eq!(cursor.set_row_position(4).unwrap(), 4);
+        let expected = make_cursor_with_granularity(2);
+        let mut expected = expected.with_header_rows(1).unwrap();
+        expected.build_index(|_, _| true).unwrap();
+        assert_eq!(*cursor.lock_index(), *expected.lock_index());
+    }
+
+    #[test]
+    fn header_rows_with_shared_index() {
+        let cursor = make_cursor().with_header_rows(1).unwrap();
+        let data = BufReader::new(Cursor::new(b"foo\nbar\nbiz\nbaz\nbuz\n"));
+        let shared =
+            CachedRowCursor::with_index(data, Separator::from(b'\n'), cursor.index().clone());
+        let err = shared.unwrap().with_header_rows(1).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(cursor.lock_index().origin, 4);
+
         let mut cursor = make_cursor();
-        cursor.granularity = 2;
+        cursor.set_row_position(2).unwrap();
+        let data = BufReader::new(Cursor::new(b"foo\nbar\nbiz\nbaz\nbuz\n"));
+        let shared =
+            CachedRowCursor::with_index(data, Separator::from(b'\n'), cursor.index().clone());
+        let err = cursor.with_header_rows(1).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        let mut buf = vec![];
+        shared.unwrap().read_row(&mut buf).unwrap();
+        assert_eq!(buf, b"foo\n");
+    }
+
+    #[test]
+    fn fixed_length_header_rows() {
+        let data = BufReader::new(Cursor::new(b"hdr\nfoobarbiz"));
+        let cursor = CachedRowCursor::with_framer(data, FixedLength::new(4), 1);
+        let mut cursor = cursor.with_header_rows(1).unwrap();
+
+        assert_eq!(cursor.header(), [b"hdr\n"]);
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 2);
+        assert_eq!(cursor.position(), 12);
+        assert_eq!(cursor.set_position(9).unwrap(), 9);
+        assert_eq!(cursor.row_position(), 1);
+    }
+
+    #[test]
+    fn get_row() {
+        let data = BufReader::with_capacity(4, Cursor::new(b"foo\r\nbar\r\nbiz\r\nbaz"));
+        let mut cursor = CachedRowCursor::new(data, b"\r\n", 2);
+        cursor.set_strip_framing(true);
+        cursor.set_position(9).unwrap();
+        assert_eq!(cursor.state, 1);
+
+        let mut buf = vec![];
+        assert_eq!(cursor.get_row(3, &mut buf).unwrap(), 3);
+        assert_eq!(buf, b"baz");
+        assert_eq!(cursor.get_row(0, &mut buf).unwrap(), 5);
+        assert_eq!(buf, b"bazfoo");
+        assert_eq!(cursor.get_row(4, &mut buf).unwrap(), 0);
+        assert_eq!(cursor.lock_index().row_length, Some(4));
+        assert_eq!(
+            cursor.lock_index().checkpoints.keys().collect::<Vec<_>>(),
+            [&0, &2, &4]
+        );
+
+        assert_eq!(cursor.position(), 9);
+        assert_eq!(cursor.row_position(), 1);
+        cursor.set_strip_framing(false);
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 1);
+        assert_eq!(buf, b"\n");
+        assert_eq!(cursor.row_position(), 2);
+    }
+
+    #[test]
+    fn granularity() {
+        let mut cursor = make_cursor_with_granularity(2);
 
         assert_eq!(cursor.seek(SeekFrom::End(0)).unwrap(), 19);
-        assert_eq!(cursor.cached_index.len(), 3);
+        assert_eq!(cursor.lock_index().checkpoints.len(), 3);
+    }
+
+    #[test]
+    fn shared_index() {
+        let data = b"id\nfoo\nbar\nbiz\nbaz\n";
+        let index = RowIndex::shared(2);
+        let reader = BufReader::new(Cursor::new(data));
+        let cursor = CachedRowCursor::with_index(reader, Separator::from(b'\n'), index.clone());
+        let mut first = cursor.unwrap().with_header_rows(1).unwrap();
+        assert_eq!(first.set_row_position(2).unwrap(), 2);
+
+        let reader = BufReader::new(Cursor::new(data));
+        let cursor = CachedRowCursor::with_index(reader, Separator::from(b'\n'), index.clone());
+        let mut second = cursor.unwrap();
+        assert_eq!(second.position(), 3);
+        assert_eq!(second.offset_of_row(2), Some(11));
+        assert_eq!(second.seek_row(SeekFrom::End(0)).unwrap(), 3);
+
+        // Rows found by the second cursor are known to the first
+        assert_eq!(index.read().unwrap().row_length(), Some(4));
+        assert_eq!(first.offset_of_row(4), Some(19));
+        let mut buf = vec![];
+        assert_eq!(first.read_row(&mut buf).unwrap(), 4);
+        assert_eq!(buf, b"biz\n");
     }
 }
@@ -0,0 +1,374 @@
+use std::io::{BufRead, Read, Seek, SeekFrom};
+
+use crate::{CachedRowCursor, RowFramer, Separator};
+
+// Cursor over an ordered list of parts read as a single stream, such as a
+// data set split into several files. Rows and bytes are numbered across all
+// parts. Each part has its own row index and ends a row, whether or not it
+// ends with a row end. Without any parts the data is empty.
+pub struct MultiRowCursor<T, F: RowFramer = Separator> {
+    parts: Vec<CachedRowCursor<T, F>>,
+    // Part holding the current position
+    current: usize,
+    // First byte and first row of each part, followed by the end of the
+    // data, known as far as all earlier parts have been measured
+    starts: Vec<(u64, u64)>,
+}
+
+impl<T: BufRead + Seek> MultiRowCursor<T> {
+    pub fn new<I, S>(readers: I, separator: S, granularity: u64) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        S: Into<Separator>,
+    {
+        Self::with_framer(readers, separator.into(), granularity)
+    }
+}
+
+impl<T: BufRead + Seek, F: RowFramer> MultiRowCursor<T, F> {
+    // Create cursor with rows of every part delimited by `framer`
+    pub fn with_framer<I>(readers: I, framer: F, granularity: u64) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        F: Clone,
+    {
+        let parts = readers
+            .into_iter()
+            .map(|reader| CachedRowCursor::with_framer(reader, framer.clone(), granularity))
+            .collect();
+        Self::from_parts(parts)
+    }
+
+    // Create cursor over `parts`, which must be at their first data row. Parts
+    // whose index is already complete, such as one loaded from a sidecar file,
+    // are skipped over without reading them.
+    pub fn from_parts(parts: Vec<CachedRowCursor<T, F>>) -> Self {
+        let mut cursor = Self {
+            parts,
+            current: 0,
+            starts: vec![(0, 0)],
+        };
+        for part in 0..cursor.parts.len() {
+            if !cursor.measure(part) {
+                break;
+            }
+        }
+        cursor
+    }
+
+    // Return the parts, each with its own row index
+    pub fn parts(&self) -> &[CachedRowCursor<T, F>] {
+        &self.parts
+    }
+
+    // Return the current part and the byte position within it
+    pub fn part_position(&self) -> (usize, u64) {
+        let pos = self
+            .parts
+            .get(self.current)
+            .map_or(0, |part| part.position());
+        (self.current, pos)
+    }
+
+    // Return current byte position
+    pub fn position(&self) -> u64 {
+        self.starts[self.current].0 + self.part_position().1
+    }
+
+    pub fn row_position(&mut self) -> u64 {
+        let row = self
+            .parts
+            .get_mut(self.current)
+            .map_or(0, |part| part.row_position());
+        self.starts[self.current].1 + row
+    }
+
+    // Set whether `read_row` returns rows without framing bytes, such as separators
+    pub fn set_strip_framing(&mut self, strip: bool) {
+        for part in &mut self.parts {
+            part.set_strip_framing(strip);
+        }
+    }
+
+    // Set byte position
+    pub fn set_position(&mut self, pos: u64) -> Result<u64, std::io::Error> {
+        if self.parts.is_empty() {
+            return Ok(0);
+        }
+        // Skip parts known to end before the position
+        let mut part = self.part_at(self.starts.partition_point(|&(byte, _)| byte <= pos));
+        loop {
+            let start = self.starts[part].0;
+            let found = self.parts[part].set_position(pos - start)?;
+            if part == self.parts.len() - 1 || !self.at_end(part)? {
+                self.current = part;
+                return Ok(start + found);
+            }
+            part = self.enter_next(part)?;
+        }
+    }
+
+    pub fn set_row_position(&mut self, row: u64) -> Result<u64, std::io::Error> {
+        if self.parts.is_empty() {
+            return Ok(0);
+        }
+        // Skip parts known to end before the row
+        let mut part = self.part_at(self.starts.partition_point(|&(_, start)| start <= row));
+        loop {
+            let start = self.starts[part].1;
+            let found = self.parts[part].set_row_position(row - start)?;
+            if part == self.parts.len() - 1 || !self.at_end(part)? {
+                self.current = part;
+                return Ok(start + found);
+            }
+            part = self.enter_next(part)?;
+        }
+    }
+
+    pub fn seek_row(&mut self, pos: SeekFrom) -> Result<u64, std::io::Error> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => pos as i64,
+            SeekFrom::Current(pos) => self.row_position() as i64 + pos,
+            SeekFrom::End(pos) => self.build_index()?.1 as i64 - 1 + pos,
+        };
+
+        if pos < 0 {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative row position",
+            ))
+        } else {
+            self.set_row_position(pos as u64)
+        }
+    }
+
+    // Append the rest of the current row to `buf`, moving on to the next part
+    // at the end of a part. Returns the number of bytes consumed.
+    pub fn read_row(&mut self, buf: &mut Vec<u8>) -> Result<usize, std::io::Error> {
+        if self.parts.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let byte_len = self.parts[self.current].read_row(buf)?;
+            if byte_len != 0 || self.current == self.parts.len() - 1 {
+                return Ok(byte_len);
+            }
+            self.current = self.enter_next(self.current)?;
+        }
+    }
+
+    // Complete the index of every part, returning the total byte and row
+    // length of the data
+    pub fn build_index(&mut self) -> Result<(u64, u64), std::io::Error> {
+        for part in self.starts.len() - 1..self.parts.len() {
+            self.parts[part].build_index(|_, _| true)?;
+            self.measure(part);
+        }
+        Ok(self.starts[self.parts.len()])
+    }
+
+    // Last part among the first `i` known to start before a target position
+    fn part_at(&self, i: usize) -> usize {
+        i.clamp(1, self.parts.len()) - 1
+    }
+
+    // Whether the cursor of `part` is at the end of its data, recording the
+    // length of the part when it is
+    fn at_end(&mut self, part: usize) -> Result<bool, std::io::Error> {
+        let cursor = &mut self.parts[part];
+        if cursor.partial || !cursor.inner.fill_buf()?.is_empty() {
+            return Ok(false);
+        }
+        cursor.read_row(&mut vec![])?;
+        Ok(true)
+    }
+
+    // Record where the part after `part` starts if the length of `part` is
+    // known, returning whether it is
+    fn measure(&mut self, part: usize) -> bool {
+        if self.starts.len() > part + 1 {
+            return true;
+        }
+
+        let (byte, row) = self.starts[part];
+        let index = self.parts[part].lock_index();
+        let end = index.length.zip(index.row_length);
+        drop(index);
+        match end {
+            Some((length, row_length)) => {
+                self.starts.push((byte + length, row + row_length));
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Move from the end of `part` to the start of the next part
+    fn enter_next(&mut self, part: usize) -> Result<usize, std::io::Error> {
+        self.measure(part);
+        self.parts[part + 1].set_row_position(0)?;
+        Ok(part + 1)
+    }
+}
+
+impl<T: BufRead + Seek, F: RowFramer> Read for MultiRowCursor<T, F> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        if self.parts.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let n = self.parts[self.current].read(buf)?;
+            if n != 0 || buf.is_empty() || self.current == self.parts.len() - 1 {
+                return Ok(n);
+            }
+            self.current = self.enter_next(self.current)?;
+        }
+    }
+}
+
+impl<T: BufRead + Seek, F: RowFramer> Seek for MultiRowCursor<T, F> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, std::io::Error> {
+        let pos = match pos {
+            SeekFrom::Start(n) => n as i64,
+            SeekFrom::Current(n) => self.position() as i64 + n,
+            SeekFrom::End(n) => self.build_index()?.0 as i64 - 1 + n,
+        };
+
+        if pos < 0 {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position",
+            ))
+        } else {
+            self.set_position(pos as u64)
+        }
+    }
+
+    fn stream_position(&mut self) -> Result<u64, std::io::Error> {
+        Ok(self.position())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MultiRowCursor;
+    use crate::{CachedRowCursor, LengthHeader};
+    use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+
+    const PARTS: [&[u8]; 4] = [b"foo\nbar", b"biz\n", b"", b"baz\nbuz"];
+
+    fn make_cursor() -> MultiRowCursor<BufReader<Cursor<&'static [u8]>>> {
+        let readers = PARTS.map(|part| BufReader::with_capacity(3, Cursor::new(part)));
+        MultiRowCursor::new(readers, b'\n', 1)
+    }
+
+    #[test]
+    fn read_rows() {
+        let mut cursor = make_cursor();
+        let mut rows = vec![];
+        loop {
+            let mut buf = vec![];
+            if cursor.read_row(&mut buf).unwrap() == 0 {
+                break;
+            }
+            rows.push((buf, cursor.row_position(), cursor.position()));
+        }
+
+        let expected: [(&[u8], u64, u64); 5] = [
+            (b"foo\n", 1, 4),
+            (b"bar", 2, 7),
+            (b"biz\n", 3, 11),
+            (b"baz\n", 4, 15),
+            (b"buz", 5, 18),
+        ];
+        assert_eq!(rows.len(), expected.len());
+        for ((buf, row, pos), (expected_buf, expected_row, expected_pos)) in
+            rows.iter().zip(expected)
+        {
+            assert_eq!(
+                (&buf[..], *row, *pos),
+                (expected_buf, expected_row, expected_pos)
+            );
+        }
+        assert_eq!(cursor.part_position(), (3, 7));
+    }
+
+    #[test]
+    fn seek() {
+        let mut cursor = make_cursor();
+        cursor.set_strip_framing(true);
+
+        let mut buf = vec![];
+        assert_eq!(cursor.seek_row(SeekFrom::Start(3)).unwrap(), 3);
+        assert_eq!(cursor.position(), 11);
+        assert_eq!(cursor.part_position(), (3, 0));
+        cursor.read_row(&mut buf).unwrap();
+        assert_eq!(buf, b"baz");
+
+        assert_eq!(cursor.seek_row(SeekFrom::Start(2)).unwrap(), 2);
+        assert_eq!(cursor.part_position(), (1, 0));
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 4);
+        assert_eq!(cursor.position(), 15);
+        assert_eq!(cursor.seek_row(SeekFrom::End(3)).unwrap(), 5);
+        assert_eq!(cursor.position(), 18);
+        assert_eq!(cursor.seek_row(SeekFrom::Current(-4)).unwrap(), 1);
+        assert_eq!(cursor.position(), 4);
+
+        assert_eq!(cursor.set_position(9).unwrap(), 9);
+        assert_eq!(cursor.row_position(), 2);
+        assert_eq!(cursor.part_position(), (1, 2));
+        assert_eq!(cursor.seek(SeekFrom::End(0)).unwrap(), 17);
+        assert_eq!(cursor.part_position(), (3, 6));
+        assert_eq!(cursor.build_index().unwrap(), (18, 5));
+
+        let mut data = vec![];
+        cursor.seek(SeekFrom::Start(2)).unwrap();
+        cursor.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"o\nbarbiz\nbaz\nbuz");
+    }
+
+    #[test]
+    fn composed_indexes() {
+        let parts = PARTS.map(|part| {
+            let mut cursor = CachedRowCursor::new(BufReader::new(Cursor::new(part)), b'\n', 1);
+            cursor.build_index(|_, _| true).unwrap();
+            cursor
+        });
+        let mut cursor = MultiRowCursor::from_parts(parts.into());
+
+        assert_eq!(cursor.seek_row(SeekFrom::Start(4)).unwrap(), 4);
+        assert_eq!(cursor.part_position(), (3, 4));
+        for part in &cursor.parts()[..3] {
+            assert_eq!(part.position(), 0);
+        }
+    }
+
+    #[test]
+    fn length_prefixed() {
+        let parts = [&b"\x01a\x02bc"[..], b"\x00\x01d"].map(|part| {
+            CachedRowCursor::with_length_prefix(Cursor::new(part), LengthHeader::U8, 1)
+        });
+        let mut cursor = MultiRowCursor::from_parts(parts.into());
+
+        assert_eq!(cursor.seek_row(SeekFrom::Start(3)).unwrap(), 3);
+        assert_eq!(cursor.position(), 6);
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 2);
+        assert_eq!(buf, b"d");
+    }
+
+    #[test]
+    fn no_parts() {
+        let readers: [BufReader<Cursor<&[u8]>>; 0] = [];
+        let mut cursor = MultiRowCursor::new(readers, b'\n', 1);
+
+        assert_eq!(cursor.build_index().unwrap(), (0, 0));
+        assert_eq!(cursor.seek_row(SeekFrom::Start(2)).unwrap(), 0);
+        assert_eq!(cursor.seek(SeekFrom::Start(2)).unwrap(), 0);
+        assert!(cursor.seek_row(SeekFrom::End(0)).is_err());
+        assert_eq!((cursor.position(), cursor.row_position()), (0, 0));
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 0);
+        assert_eq!(cursor.read_to_end(&mut buf).unwrap(), 0);
+    }
+}
@@ -0,0 +1,249 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+use crate::{CachedRowCursor, RowIndex, Separator};
+
+const CHUNK_BUF_SIZE: usize = 64 * 1024;
+
+impl CachedRowCursor<BufReader<File>> {
+    // Open file at `path` with its complete row index built by `threads` worker
+    // threads. Each thread counts separators in its own byte range, the counts
+    // are stitched into global row numbers, and a second pass records the
+    // checkpoints falling inside each range.
+    pub fn open_parallel<P: AsRef<Path>, S: Into<Separator>>(
+        path: P,
+        separator: S,
+        granularity: u64,
+        threads: usize,
+    ) -> Result<Self, std::io::Error> {
+        let path = path.as_ref();
+        let separator = separator.into();
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+
+        // Occurrences of a self-overlapping separator depend on where the
+        // search starts, so those can only be found by a single sequential pass
+        let threads = if separator.self_overlapping() {
+            1
+        } else {
+            threads.max(1) as u64
+        };
+        let chunk_len = size.div_ceil(threads).max(1);
+        let chunks: Vec<(u64, u64)> = (0..size)
+            .step_by(chunk_len as usize)
+            .map(|start| (start, chunk_len.min(size - start)))
+            .collect();
+
+        // First pass: count separators in each chunk
+        let counts = run_chunks(&chunks, |&(start, len)| {
+            let mut count = 0;
+            let mut last_end = None;
+            find_separators(path, size, &separator, start, len, |end| {
+                count += 1;
+                last_end = Some(end);
+            })?;
+            Ok((count, last_end))
+        })?;
+
+        // Second pass: record checkpoints using the row number each chunk starts at
+        let bases: Vec<(u64, u64, u64)> = chunks
+            .iter()
+            .zip(counts.iter().scan(0, |base, &(count, _)| {
+                let start_row = *base;
+                *base += count;
+                Some(start_row)
+            }))
+            .map(|(&(start, len), base)| (start, len, base))
+            .collect();
+        let checkpoints = run_chunks(&bases, |&(start, len, base)| {
+            let mut row = base;
+            let mut found = vec![];
+            find_separators(path, size, &separator, start, len, |end| {
+                row += 1;
+                if row.is_multiple_of(granularity) {
+                    found.push((row, end));
+                }
+            })?;
+            Ok(found)
+        })?;
+
+        let mut index = RowIndex::new(granularity);
+        for (row, byte) in checkpoints.into_iter().flatten() {
+            index.insert(row, byte);
+        }
+
+        // A last row without a trailing separator still counts as a row
+        let mut rows: u64 = counts.iter().map(|&(count, _)| count).sum();
+        let last_end = counts.iter().rev().find_map(|&(_, last_end)| last_end);
+        if size > 0 && last_end != Some(size) {
+            rows += 1;
+            if rows.is_multiple_of(granularity) {
+                index.insert(rows, size);
+            }
+        }
+        index.length = Some(size);
+        index.row_length = Some(rows);
+
+        Self::with_index(
+            BufReader::new(file),
+            separator,
+            Arc::new(RwLock::new(index)),
+        )
+    }
+}
+
+// Run `f` over every chunk on its own thread, returning results in chunk order
+fn run_chunks<C, R, F>(chunks: &[C], f: F) -> Result<Vec<R>, std::io::Error>
+where
+    C: Sync,
+    R: Send,
+    F: Fn(&C) -> Result<R, std::io::Error> + Sync,
+{
+    thread::scope(|s| {
+        let handles: Vec<_> = chunks.iter().map(|chunk| s.spawn(|| f(chunk))).collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|err| std::panic::resume_unwind(err))
+            })
+            .collect()
+    })
+}
+
+// Call `f` with the end offset of every separator ending within `len` bytes
+// after `start`, reading around the range to recognise straddling separators
+fn find_separators<F>(
+    path: &Path,
+    size: u64,
+    separator: &Separator,
+    start: u64,
+    len: u64,
+    mut f: F,
+) -> Result<(), std::io::Error>
+where
+    F: FnMut(u64),
+{
+    let range_end = start + len;
+    let read_start = start.saturating_sub(separator.lookbehind() as u64);
+    let read_end = (range_end + separator.lookahead() as u64).min(size);
+
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(read_start))?;
+    let mut chunk = file.take(read_end - read_start);
+
+    let mut buf = vec![0; CHUNK_BUF_SIZE];
+    let mut offset = read_start;
+    let mut matched = 0;
+    loop {
+        let n = chunk.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+
+        let mut i = 0;
+        while let Some(end) = separator.find(&mut matched, &buf[i..n]) {
+            i += end;
+            let end = offset + i as u64;
+            if end > range_end {
+                return Ok(());
+            }
+            if end > start {
+                f(end);
+            }
+        }
+        offset += n as u64;
+    }
+
+    if offset < read_end {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "file shrank while building row index",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::temp_file;
+    use crate::{CachedRowCursor, Separator};
+    use std::io::{BufReader, Cursor, SeekFrom};
+
+    fn assert_same_index<S: Into<Separator> + Clone>(
+        name: &str,
+        data: &'static [u8],
+        separator: S,
+    ) {
+        let path = temp_file(name, data);
+
+        for granularity in 1..=5 {
+            let mut expected = CachedRowCursor::new(
+                BufReader::new(Cursor::new(data)),
+                separator.clone(),
+                granularity,
+            );
+            expected.build_index(|_, _| true).unwrap();
+
+            for threads in 1..=8 {
+                let cursor =
+                    CachedRowCursor::open_parallel(&path, separator.clone(), granularity, threads)
+                        .unwrap();
+                assert_eq!(
+                    cursor.lock_index().checkpoints,
+                    expected.lock_index().checkpoints
+                );
+                assert_eq!(cursor.lock_index().offsets, expected.lock_index().offsets);
+                assert_eq!(cursor.lock_index().length, expected.lock_index().length);
+                assert_eq!(
+                    cursor.lock_index().row_length,
+                    expected.lock_index().row_length
+                );
+            }
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn same_as_sequential() {
+        assert_same_index("trailing", b"foo\nbar\nbiz\nbaz\nbuz\n", b"\n");
+        assert_same_index("no-trailing", b"foo\nbar\n\n\nbiz\nbaz\nbuz", b"\n");
+        assert_same_index("empty", b"", b"\n");
+        assert_same_index("single", b"foo", b"\n");
+    }
+
+    #[test]
+    fn multi_byte_separator() {
+        assert_same_index("crlf", b"foo\r\nbar\r\r\n\r\nbiz\nbaz\r\nbuz\r", b"\r\n");
+        assert_same_index("record", b"a|||\nb||\n||\n|\n||c||\n", b"||\n");
+        assert_same_index("overlapping", b"aaabaaaaba", b"aa");
+    }
+
+    #[test]
+    fn universal_newline() {
+        let newline = Separator::universal_newline();
+        assert_same_index("newline", b"a\nb\r\nc\rd\r\r\n\n\re\r", newline.clone());
+        assert_same_index("newline-crlf", b"\r\n\r\n\r\r\n\n\r", newline.clone());
+        assert_same_index("newline-trailing", b"\r\na\r\nb", newline);
+    }
+
+    #[test]
+    fn seek_with_parallel_index() {
+        let path = temp_file("seek", b"foo\nbar\nbiz\nbaz\nbuz\n");
+
+        let mut cursor = CachedRowCursor::open_parallel(&path, b'\n', 1, 3).unwrap();
+        assert_eq!(cursor.seek_row(SeekFrom::End(-1)).unwrap(), 3);
+        assert_eq!(cursor.position(), 12);
+
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 4);
+        assert_eq!(buf, b"baz\n");
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,213 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::sync::{Arc, RwLock};
+
+use crate::{CachedRowCursor, Row, RowFramer, RowIndex, Separator};
+
+// Reader of rows of a file that can be shared between threads. Rows are read
+// with positional I/O rather than by seeking a shared file handle, and the row
+// index is shared by all threads.
+pub struct PositionalRowReader<F: RowFramer = Separator> {
+    file: File,
+    framer: F,
+    index: Arc<RwLock<RowIndex>>,
+    // Whether rows are returned without framing bytes
+    strip_framing: bool,
+}
+
+impl PositionalRowReader {
+    pub fn new<S: Into<Separator>>(file: File, separator: S, granularity: u64) -> Self {
+        Self::with_framer(file, separator.into(), granularity)
+    }
+}
+
+impl<F: RowFramer + Clone> PositionalRowReader<F> {
+    // Create reader with rows delimited by `framer`
+    pub fn with_framer(file: File, framer: F, granularity: u64) -> Self {
+        Self::with_index(file, framer, RowIndex::shared(granularity))
+    }
+
+    // Create reader sharing `index` with cursors over the same file and using
+    // the same framing
+    pub fn with_index(file: File, framer: F, index: Arc<RwLock<RowIndex>>) -> Self {
+        Self {
+            file,
+            framer,
+            index,
+            strip_framing: false,
+        }
+    }
+
+    // Return the row index, which can be shared with cursors through `with_index`
+    pub fn index(&self) -> &Arc<RwLock<RowIndex>> {
+        &self.index
+    }
+
+    // Set whether rows are returned without framing bytes
+    pub fn set_strip_framing(&mut self, strip: bool) {
+        self.strip_framing = strip;
+    }
+
+    // Append row `row` to `buf`, returning the number of bytes in the row or 0
+    // past the last row. The index is extended as rows are scanned.
+    pub fn read_row_at(&self, row: u64, buf: &mut Vec<u8>) -> Result<usize, std::io::Error> {
+        let mut cursor = self.cursor()?;
+        if cursor.set_row_position(row)? != row {
+            return Ok(0);
+        }
+        cursor.read_row(buf)
+    }
+
+    // Return rows `range.start` up to `range.end`. The index is extended as
+    // rows are scanned.
+    pub fn read_rows_at(&self, range: Range<u64>) -> Result<Vec<Row>, std::io::Error> {
+        self.cursor()?.rows_range(range)?.collect()
+    }
+
+    // Create cursor over the file for the calling thread
+    fn cursor(&self) -> Result<CachedRowCursor<BufReader<FileAt<'_>>, F>, std::io::Error> {
+        let reader = BufReader::new(FileAt {
+            file: &self.file,
+            pos: 0,
+        });
+        let mut cursor =
+            CachedRowCursor::with_index(reader, self.framer.clone(), self.index.clone())?;
+        cursor.set_strip_framing(self.strip_framing);
+        Ok(cursor)
+    }
+}
+
+// Reader of a file at its own position, leaving the file position untouched
+struct FileAt<'a> {
+    file: &'a File,
+    pos: u64,
+}
+
+impl Read for FileAt<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = read_at(self.file, buf, self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for FileAt<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(pos) => (pos, 0),
+            SeekFrom::Current(offset) => (self.pos, offset),
+            SeekFrom::End(offset) => (self.file.metadata()?.len(), offset),
+        };
+        self.pos = base.checked_add_signed(offset).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+// Moves the file position, which is never used otherwise
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PositionalRowReader;
+    use crate::test_util::temp_file;
+    use crate::{CachedRowCursor, LengthHeader, LengthPrefixed, Separator};
+    use std::fs::File;
+    use std::io::{BufReader, SeekFrom};
+
+    #[test]
+    fn concurrent_reads() {
+        let data: Vec<u8> = (0..1000)
+            .flat_map(|i| format!("row {i}\n").into_bytes())
+            .collect();
+        let path = temp_file("positional-concurrent", &data);
+        let reader = PositionalRowReader::new(File::open(&path).unwrap(), b'\n', 16);
+
+        std::thread::scope(|scope| {
+            for thread in 0..8 {
+                let reader = &reader;
+                scope.spawn(move || {
+                    for i in (thread..1000).rev().step_by(7) {
+                        let mut buf = vec![];
+                        assert_eq!(reader.read_row_at(i, &mut buf).unwrap(), buf.len());
+                        assert_eq!(buf, format!("row {i}\n").as_bytes());
+                    }
+                });
+            }
+        });
+
+        let mut buf = vec![];
+        assert_eq!(reader.read_row_at(1000, &mut buf).unwrap(), 0);
+        let index = reader.index().read().unwrap();
+        assert_eq!(index.row_length(), Some(1000));
+        assert_eq!(index.checkpoints().count(), 63);
+        drop(index);
+
+        let rows = reader.read_rows_at(998..1002).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            (rows[0].index, rows[0].offset),
+            (998, data.len() as u64 - 16)
+        );
+        assert_eq!(rows[1].bytes, b"row 999\n");
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn shared_with_cursor() {
+        let path = temp_file("positional-shared", b"id\nfoo\nbar\nbiz");
+        let reader = BufReader::new(File::open(&path).unwrap());
+        let cursor = CachedRowCursor::new(reader, b'\n', 1);
+        let mut cursor = cursor.with_header_rows(1).unwrap();
+
+        let file = File::open(&path).unwrap();
+        let separator = Separator::from(b'\n');
+        let mut reader = PositionalRowReader::with_index(file, separator, cursor.index().clone());
+        reader.set_strip_framing(true);
+        let mut buf = vec![];
+        assert_eq!(reader.read_row_at(2, &mut buf).unwrap(), 3);
+        assert_eq!(buf, b"biz");
+
+        assert_eq!(cursor.offset_of_row(3), Some(14));
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 2);
+        assert_eq!(cursor.position(), 11);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn length_prefixed() {
+        let path = temp_file("positional-length-prefixed", b"\x03foo\x00\x02ab");
+        let file = File::open(&path).unwrap();
+        let mut reader =
+            PositionalRowReader::with_framer(file, LengthPrefixed::new(LengthHeader::U8), 1);
+        reader.set_strip_framing(true);
+
+        let rows = reader.read_rows_at(0..3).unwrap();
+        let rows: Vec<_> = rows.iter().map(|row| &row.bytes[..]).collect();
+        assert_eq!(rows, [&b"foo"[..], b"", b"ab"]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn is_sync() {
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<PositionalRowReader>();
+        assert_sync::<PositionalRowReader<LengthPrefixed>>();
+    }
+}
@@ -0,0 +1,170 @@
+use std::io::{BufRead, Seek};
+use std::ops::Bound;
+
+use crate::{CachedRowCursor, RowFramer, RowIndex};
+
+impl<T, F: RowFramer> CachedRowCursor<T, F> {
+    // Return the byte offset of row `row` if it is known without reading
+    pub fn offset_of_row(&self, row: u64) -> Option<u64> {
+        let index = self.lock_index();
+        if let Some(len) = self.framer.fixed_len() {
+            let length = index.length?;
+            return (row <= index.row_length?).then(|| (index.origin + row * len).min(length));
+        }
+
+        match index.get(row) {
+            Some(byte) => Some(byte),
+            None => self
+                .known_rows(&index)
+                .find_map(|(known_row, byte)| (known_row == row).then_some(byte)),
+        }
+    }
+
+    // Return the row holding byte `byte` if it is known without reading
+    pub fn row_at_offset(&self, byte: u64) -> Option<u64> {
+        let index = self.lock_index();
+        if byte < index.origin || index.length.is_some_and(|length| byte >= length) {
+            return None;
+        }
+        if let Some(len) = self.framer.fixed_len() {
+            return index.length.map(|_| (byte - index.origin) / len);
+        }
+
+        // The row is known if the rows starting around `byte` are both known
+        let before = index
+            .offsets
+            .range(..=byte)
+            .next_back()
+            .map(|(&byte, &row)| (row, byte))
+            .into_iter()
+            .chain(self.known_rows(&index).filter(|&(_, start)| start <= byte))
+            .max_by_key(|&(_, start)| start)?;
+        let after = index
+            .offsets
+            .range((Bound::Excluded(byte), Bound::Unbounded))
+            .next()
+            .map(|(&byte, &row)| (row, byte))
+            .into_iter()
+            .chain(self.known_rows(&index).filter(|&(_, start)| start > byte))
+            .min_by_key(|&(_, start)| start)?;
+
+        (after.0 == before.0 + 1).then_some(before.0)
+    }
+
+    // Row starts known outside the checkpoints: the current position between
+    // rows, and the end of the data
+    fn known_rows(&self, index: &RowIndex) -> impl Iterator<Item = (u64, u64)> {
+        let current = (!self.partial).then_some((self.row_pos, self.pos));
+        let end = index.row_length.zip(index.length);
+        current.into_iter().chain(end)
+    }
+}
+
+impl<T: BufRead + Seek, F: RowFramer> CachedRowCursor<T, F> {
+    // Return the byte offset of row `row`, scanning for it if needed and then
+    // returning to the current position, or `None` past the last row
+    pub fn find_offset_of_row(&mut self, row: u64) -> Result<Option<u64>, std::io::Error> {
+        if let Some(byte) = self.offset_of_row(row) {
+            return Ok(Some(byte));
+        }
+
+        let mark = self.mark();
+        let found = self
+            .set_row_position(row)
+            .map(|found| (found == row).then_some(self.pos));
+        self.restore(mark)?;
+        found
+    }
+
+    // Return the row holding byte `byte`, scanning for it if needed and then
+    // returning to the current position, or `None` outside the data rows
+    pub fn find_row_at_offset(&mut self, byte: u64) -> Result<Option<u64>, std::io::Error> {
+        if let Some(row) = self.row_at_offset(byte) {
+            return Ok(Some(row));
+        }
+        if byte < self.lock_index().origin {
+            return Ok(None);
+        }
+
+        let mark = self.mark();
+        let found = self.set_position(byte).and_then(|pos| {
+            let in_data = pos == byte && !self.inner.fill_buf()?.is_empty();
+            Ok(in_data.then_some(self.row_pos))
+        });
+        self.restore(mark)?;
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CachedRowCursor, FixedLength};
+    use std::io::{BufReader, Cursor, SeekFrom};
+
+    fn make_cursor() -> CachedRowCursor<BufReader<Cursor<&'static [u8; 20]>>> {
+        let data = BufReader::new(Cursor::new(b"foo\nbar\nbiz\nbaz\nbuz\n"));
+        CachedRowCursor::new(data, b'\n', 2)
+    }
+
+    #[test]
+    fn known_without_reading() {
+        let mut cursor = make_cursor();
+        assert_eq!(cursor.offset_of_row(0), Some(0));
+        assert_eq!(cursor.offset_of_row(1), None);
+        assert_eq!(cursor.row_at_offset(0), None);
+
+        cursor.set_row_position(3).unwrap();
+        assert_eq!(cursor.offset_of_row(2), Some(8));
+        assert_eq!(cursor.offset_of_row(3), Some(12));
+        assert_eq!(cursor.row_at_offset(7), None);
+        assert_eq!(cursor.row_at_offset(9), Some(2));
+        assert_eq!(cursor.row_at_offset(11), Some(2));
+        assert_eq!(cursor.row_at_offset(12), None);
+        assert_eq!(cursor.row_at_offset(u64::MAX), None);
+
+        cursor.seek_row(SeekFrom::End(0)).unwrap();
+        assert_eq!(cursor.offset_of_row(5), Some(20));
+        assert_eq!(cursor.row_at_offset(3), None);
+        assert_eq!(cursor.row_at_offset(16), Some(4));
+        assert_eq!(cursor.row_at_offset(19), Some(4));
+        assert_eq!(cursor.row_at_offset(20), None);
+    }
+
+    #[test]
+    fn scanning() {
+        let mut cursor = make_cursor();
+        cursor.set_position(5).unwrap();
+
+        assert_eq!(cursor.find_offset_of_row(1).unwrap(), Some(4));
+        assert_eq!(cursor.find_offset_of_row(3).unwrap(), Some(12));
+        assert_eq!(cursor.find_row_at_offset(3).unwrap(), Some(0));
+        assert_eq!(cursor.find_row_at_offset(4).unwrap(), Some(1));
+        assert_eq!(cursor.find_row_at_offset(19).unwrap(), Some(4));
+        assert_eq!(cursor.find_row_at_offset(20).unwrap(), None);
+        assert_eq!(cursor.find_offset_of_row(5).unwrap(), Some(20));
+        assert_eq!(cursor.find_offset_of_row(6).unwrap(), None);
+
+        assert_eq!(cursor.position(), 5);
+        assert_eq!(cursor.row_position(), 1);
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 3);
+        assert_eq!(buf, b"ar\n");
+    }
+
+    #[test]
+    fn fixed_length() {
+        let data = BufReader::new(Cursor::new(b"hdr\nfoobarbiz"));
+        let cursor = CachedRowCursor::with_framer(data, FixedLength::new(4), 1);
+        let mut cursor = cursor.with_header_rows(1).unwrap();
+
+        assert_eq!(cursor.offset_of_row(0), Some(4));
+        assert_eq!(cursor.offset_of_row(2), Some(12));
+        assert_eq!(cursor.offset_of_row(3), Some(13));
+        assert_eq!(cursor.offset_of_row(4), None);
+        assert_eq!(cursor.row_at_offset(3), None);
+        assert_eq!(cursor.row_at_offset(11), Some(1));
+        assert_eq!(cursor.row_at_offset(12), Some(2));
+        assert_eq!(cursor.row_at_offset(13), None);
+        assert_eq!(cursor.find_row_at_offset(2).unwrap(), None);
+    }
+}
@@ -0,0 +1,473 @@
+use std::io::{BufRead, Seek};
+
+use crate::{CachedRowCursor, RowFramer};
+
+// Bytes read at a time when scanning backwards
+const BLOCK_SIZE: u64 = 8 * 1024;
+
+// Row read by `RowsRev`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RevRow {
+    // Row number, if known
+    pub index: Option<u64>,
+    // Byte offset the row was read from
+    pub offset: u64,
+    pub bytes: Vec<u8>,
+}
+
+// Iterator over rows in reverse order, created by `rows_rev` and `rows_rev_from_end`
+pub struct RowsRev<'a, T> {
+    cursor: &'a mut CachedRowCursor<T>,
+    // Position when reading from the end of the data without moving the cursor
+    from_end: Option<FromEnd>,
+    done: bool,
+}
+
+// Position of a `RowsRev` reading from the end of the data
+struct FromEnd {
+    // End of the data
+    length: u64,
+    // Start of the last row returned
+    start: u64,
+    // Number of rows returned
+    rows: u64,
+}
+
+impl<T: BufRead + Seek> CachedRowCursor<T> {
+    // Move to the start of the row before the current position, or of the
+    // current row if partly read, appending its bytes up to the current position
+    // to `buf`. Returns the number of bytes read, which is 0 at the first row.
+    pub fn read_row_back(&mut self, buf: &mut Vec<u8>) -> Result<usize, std::io::Error> {
+        let end = self.pos;
+        if end <= self.lock_index().origin {
+            return Ok(0);
+        }
+
+        let start = self.row_start_before(end)?;
+        let row = if self.partial {
+            self.row_pos
+        } else {
+            self.row_pos - 1
+        };
+
+        self.read_row_span(start, end, buf)?;
+
+        self.jump_to_checkpoint(row, start)?;
+        if row.is_multiple_of(self.granularity) {
+            self.insert_checkpoint(row, start);
+        }
+
+        Ok((end - start) as usize)
+    }
+
+    // Iterate over rows before the current position, last first
+    pub fn rows_rev(&mut self) -> RowsRev<'_, T> {
+        RowsRev {
+            cursor: self,
+            from_end: None,
+            done: false,
+        }
+    }
+
+    // Iterate over rows from the end of the data backwards, last first, without
+    // scanning the data forward or moving the cursor. Row numbers are `None`
+    // until the row count is known, either from the index or once the first
+    // row is reached, which records the row count. In follow mode a partly
+    // written last row is skipped.
+    pub fn rows_rev_from_end(&mut self) -> Result<RowsRev<'_, T>, std::io::Error> {
+        let length = self.lock_index().length;
+        let length = match length {
+            Some(length) => length,
+            None => self.data_end()?,
+        };
+
+        Ok(RowsRev {
+            cursor: self,
+            from_end: Some(FromEnd {
+                length,
+                start: length,
+                rows: 0,
+            }),
+            done: false,
+        })
+    }
+
+    // Return the last `n` rows in order, scanning backwards from the end of the
+    // data so the time taken depends on the rows returned rather than on the
+    // size of the data. The cursor position is unchanged. Rows are added to the
+    // index if the row count is known.
+    pub fn tail(&mut self, n: usize) -> Result<Vec<Vec<u8>>, std::io::Error> {
+        let mut rows = self
+            .rows_rev_from_end()?
+            .take(n)
+            .map(|row| row.map(|row| row.bytes))
+            .collect::<Result<Vec<_>, _>>()?;
+        rows.reverse();
+        Ok(rows)
+    }
+
+    // Find the start of the row holding the byte before `end`, reading back in blocks
+    fn row_start_before(&mut self, end: u64) -> Result<u64, std::io::Error> {
+        if self.framer.self_overlapping() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "cannot read backwards with a self-overlapping separator",
+            ));
+        }
+
+        // No row starts before the last checkpoint
+        let floor = {
+            let index = self.lock_index();
+            index
+                .offsets
+                .range(..end)
+                .next_back()
+                .map_or(index.origin, |(&byte, _)| byte)
+        };
+        // Bytes past each block needed to recognise separators straddling it
+        let overlap = self.framer.lookbehind() as u64;
+
+        let mut block = vec![];
+        let mut block_end = end;
+        while block_end > floor {
+            let block_start = block_end.saturating_sub(BLOCK_SIZE).max(floor);
+            block.clear();
+            self.read_span(block_start, (block_end + overlap).min(end), &mut block)?;
+
+            let limit = (end - block_start) as usize;
+            if let Some(i) = self.framer.rfind(&block, limit) {
+                return Ok(block_start + i as u64);
+            }
+            block_end = block_start;
+        }
+
+        Ok(floor)
+    }
+
+    // Append the row from `start` to `end` to `buf`, without framing bytes if
+    // stripping is enabled. Returns whether the row ends with a row end.
+    fn read_row_span(
+        &mut self,
+        start: u64,
+        end: u64,
+        buf: &mut Vec<u8>,
+    ) -> Result<bool, std::io::Error> {
+        let buf_start = buf.len();
+        self.read_span(start, end, buf)?;
+        let payload = self.framer.payload(&buf[buf_start..]);
+        let complete = buf_start + payload.end < buf.len();
+        if self.strip_framing {
+            buf.truncate(buf_start + payload.end);
+        }
+        Ok(complete)
+    }
+}
+
+impl<T: BufRead + Seek> RowsRev<'_, T> {
+    // Move to the row before the cursor position
+    fn next_at_cursor(&mut self) -> Result<Option<RevRow>, std::io::Error> {
+        let mut bytes = vec![];
+        if self.cursor.read_row_back(&mut bytes)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(RevRow {
+            index: Some(self.cursor.row_pos),
+            offset: self.cursor.pos,
+            bytes,
+        }))
+    }
+
+    // Read the row before the last row returned, leaving the cursor in place
+    fn next_from_end(&mut self) -> Result<Option<RevRow>, std::io::Error> {
+        let Some(from_end) = &mut self.from_end else {
+            return Ok(None);
+        };
+        let cursor = &mut *self.cursor;
+        let (origin, row_length) = {
+            let index = cursor.lock_index();
+            (index.origin, index.row_length)
+        };
+
+        let mut bytes = vec![];
+        let start = loop {
+            if from_end.start <= origin {
+                return Ok(None);
+            }
+            let start = cursor.row_start_before(from_end.start)?;
+            let complete = cursor.read_row_span(start, from_end.start, &mut bytes)?;
+
+            // A partly written last row is not data yet
+            if cursor.follow && !complete && from_end.rows == 0 {
+                from_end.length = start;
+                from_end.start = start;
+                bytes.clear();
+                continue;
+            }
+            break start;
+        };
+        from_end.start = start;
+        from_end.rows += 1;
+
+        let index = if start == origin {
+            // The rows returned are all the rows
+            let mut index = cursor.lock_index_mut();
+            index.length = Some(from_end.length);
+            index.row_length = Some(from_end.rows);
+            Some(0)
+        } else {
+            row_length.map(|row_length| row_length - from_end.rows)
+        };
+        if let Some(row) = index.filter(|row| row.is_multiple_of(cursor.granularity)) {
+            cursor.insert_checkpoint(row, start);
+        }
+
+        Ok(Some(RevRow {
+            index,
+            offset: start,
+            bytes,
+        }))
+    }
+}
+
+impl<T: BufRead + Seek> Iterator for RowsRev<'_, T> {
+    type Item = Result<RevRow, std::io::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let row = if self.from_end.is_some() {
+            self.next_from_end()
+        } else {
+            self.next_at_cursor()
+        };
+        match row {
+            Ok(Some(row)) => Some(Ok(row)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{after_preamble, Counting};
+    use crate::{CachedRowCursor, Separator};
+    use std::io::{BufReader, Cursor, ErrorKind, Seek, SeekFrom};
+
+    fn forward_rows(data: &[u8], separator: &Separator) -> Vec<(u64, Vec<u8>)> {
+        let reader = BufReader::new(Cursor::new(data.to_vec()));
+        let mut cursor = CachedRowCursor::new(reader, separator.clone(), 1);
+        cursor
+            .rows()
+            .map(|row| row.map(|row| (row.offset, row.bytes)))
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn same_as_forward() {
+        let cases: [(&[u8], Separator); 6] = [
+            (b"foo\nbar\nbiz\nbaz\nbuz\n", Separator::from(b'\n')),
+            (b"foo\nbar\n\n\nbiz", Separator::from(b'\n')),
+            (b"\r\nfoo\r\r\n\r\nbar\rbiz\r\n\r", Separator::from(b"\r\n")),
+            (b"a|||\nb||\n||\n|\n||c||\n", Separator::from(b"||\n")),
+            (b"a\nb\r\nc\rd\r\r\n\n\re\r", Separator::universal_newline()),
+            (b"\r\n\r\n\r\r\n\n\r", Separator::universal_newline()),
+        ];
+
+        for (data, separator) in cases {
+            let expected = forward_rows(data, &separator);
+
+            for granularity in [1, 2, 3] {
+                let reader = BufReader::with_capacity(3, Cursor::new(data));
+                let mut cursor = CachedRowCursor::new(reader, separator.clone(), granularity);
+                cursor.seek(SeekFrom::End(1)).unwrap();
+
+                let mut rows = cursor
+                    .rows_rev()
+                    .map(|row| row.map(|row| (row.index, row.offset, row.bytes)))
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap();
+                rows.reverse();
+                assert_eq!(rows.len(), expected.len());
+                for (i, ((index, offset, bytes), (expected_offset, expected_bytes))) in
+                    rows.into_iter().zip(&expected).enumerate()
+                {
+                    assert_eq!(index, Some(i as u64));
+                    assert_eq!(offset, *expected_offset);
+                    assert_eq!(&bytes, expected_bytes);
+                }
+
+                // Row numbers are known once the first row is reached
+                let reader = BufReader::with_capacity(3, Cursor::new(data));
+                let mut cursor = CachedRowCursor::new(reader, separator.clone(), granularity);
+                for known in [false, true] {
+                    let rows = cursor.rows_rev_from_end().unwrap();
+                    let mut rows = rows.collect::<Result<Vec<_>, _>>().unwrap();
+                    rows.reverse();
+                    assert_eq!(rows.len(), expected.len());
+                    for (i, (row, (expected_offset, expected_bytes))) in
+                        rows.into_iter().zip(&expected).enumerate()
+                    {
+                        let index = (known || i == 0).then_some(i as u64);
+                        assert_eq!(row.index, index);
+                        assert_eq!(row.offset, *expected_offset);
+                        assert_eq!(&row.bytes, expected_bytes);
+                    }
+                    let row_length = cursor.lock_index().row_length;
+                    assert_eq!(row_length, Some(expected.len() as u64));
+                    assert_eq!(cursor.position(), 0);
+                }
+
+                let reader = BufReader::with_capacity(3, Cursor::new(data));
+                let mut cursor = CachedRowCursor::new(reader, separator.clone(), granularity);
+                for n in 0..expected.len() + 2 {
+                    let tail = cursor.tail(n).unwrap();
+                    let skip = expected.len().saturating_sub(n);
+                    let expected_tail: Vec<_> =
+                        expected[skip..].iter().map(|(_, bytes)| bytes).collect();
+                    assert_eq!(tail.iter().collect::<Vec<_>>(), expected_tail);
+                }
+                assert_eq!(cursor.position(), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn partial_row() {
+        let data = BufReader::new(Cursor::new(b"foo\nbar\nbiz\n"));
+        let mut cursor = CachedRowCursor::new(data, b'\n', 1);
+        cursor.set_position(6).unwrap();
+
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row_back(&mut buf).unwrap(), 2);
+        assert_eq!(buf, b"ba");
+        assert_eq!(cursor.position(), 4);
+        assert_eq!(cursor.row_position(), 1);
+
+        cursor.set_strip_framing(true);
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row_back(&mut buf).unwrap(), 4);
+        assert_eq!(buf, b"foo");
+        assert_eq!(cursor.row_position(), 0);
+        assert_eq!(cursor.read_row_back(&mut buf).unwrap(), 0);
+
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 4);
+        assert_eq!(buf, b"foo");
+    }
+
+    #[test]
+    fn header_rows() {
+        let data = BufReader::new(Cursor::new(b"id\nfoo\nbar"));
+        let mut cursor = CachedRowCursor::new(data, b'\n', 1)
+            .with_header_rows(1)
+            .unwrap();
+
+        assert_eq!(cursor.tail(5).unwrap(), [&b"foo\n"[..], b"bar"]);
+        cursor.seek_row(SeekFrom::End(0)).unwrap();
+        let rows = cursor.rows_rev().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!((rows[0].index, rows[0].offset), (Some(0), 3));
+    }
+
+    #[test]
+    fn scroll_from_end() {
+        let data = BufReader::with_capacity(4, Cursor::new(b"foo\nbar\nbiz\nbaz\nbuz\nb"));
+        let mut cursor = CachedRowCursor::new(data, b'\n', 2);
+        cursor.set_follow(true);
+        cursor.set_strip_framing(true);
+        cursor.set_position(5).unwrap();
+
+        let rows = cursor.rows_rev_from_end().unwrap().take(2);
+        let rows = rows.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!((rows[0].index, rows[0].offset), (None, 16));
+        assert_eq!(rows[0].bytes, b"buz");
+        assert_eq!(rows[1].bytes, b"baz");
+        assert_eq!(cursor.position(), 5);
+        assert_eq!(cursor.lock_index().row_length, None);
+
+        // Row numbers are filled in once the row count is known
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 4);
+        let rows = cursor.rows_rev_from_end().unwrap().take(2);
+        let rows = rows.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!((rows[0].index, rows[0].offset), (Some(4), 16));
+        assert_eq!((rows[1].index, rows[1].offset), (Some(3), 12));
+
+        cursor.set_row_position(0).unwrap();
+        let mut buf = vec![];
+        cursor.read_row(&mut buf).unwrap();
+        assert_eq!(buf, b"foo");
+    }
+
+    #[test]
+    fn long_rows() {
+        let mut data = vec![b'a'; 20_000];
+        data.push(b'\n');
+        data.extend_from_slice(&[b'b'; 30_000]);
+        data.extend_from_slice(b"\r\n");
+        let separator = Separator::universal_newline();
+        let mut cursor = CachedRowCursor::new(BufReader::new(Cursor::new(data)), separator, 1);
+
+        let tail = cursor.tail(2).unwrap();
+        assert_eq!(
+            tail.iter().map(Vec::len).collect::<Vec<_>>(),
+            [20_001, 30_002]
+        );
+    }
+
+    #[test]
+    fn tail_without_index() {
+        let data = b"0123456\n".repeat(100_000);
+        let reader = BufReader::new(Counting::new(data));
+        let mut cursor = CachedRowCursor::new(reader, b'\n', 1);
+
+        let tail = cursor.tail(3).unwrap();
+        assert_eq!(tail, [b"0123456\n"; 3]);
+        assert!(cursor.inner.get_ref().read < 64 * 1024);
+        assert_eq!(cursor.lock_index().row_length, None);
+    }
+
+    #[test]
+    fn reader_after_preamble() {
+        let reader = after_preamble(b"foo\nbar\nbiz\n");
+        let mut cursor = CachedRowCursor::new(reader, b'\n', 1);
+
+        assert_eq!(cursor.tail(2).unwrap(), [b"bar\n", b"biz\n"]);
+        let mut buf = vec![];
+        cursor.read_row(&mut buf).unwrap();
+        assert_eq!(buf, b"foo\n");
+
+        buf.clear();
+        assert_eq!(cursor.read_row_back(&mut buf).unwrap(), 4);
+        assert_eq!(buf, b"foo\n");
+        assert_eq!(cursor.position(), 0);
+        buf.clear();
+        cursor.read_row(&mut buf).unwrap();
+        assert_eq!(buf, b"foo\n");
+
+        let rows = cursor.rows_rev_from_end().unwrap();
+        let offsets = rows.map(|row| row.unwrap().offset).collect::<Vec<_>>();
+        assert_eq!(offsets, [8, 4, 0]);
+        assert_eq!(cursor.lock_index().length, Some(12));
+        buf.clear();
+        cursor.read_row(&mut buf).unwrap();
+        assert_eq!(buf, b"bar\n");
+    }
+
+    #[test]
+    fn self_overlapping() {
+        let data = BufReader::new(Cursor::new(b"aaa"));
+        let mut cursor = CachedRowCursor::new(data, b"aa", 1);
+        let err = cursor.tail(1).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+    }
+}
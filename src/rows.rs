@@ -0,0 +1,179 @@
+use std::io::{BufRead, Seek};
+use std::ops::Range;
+
+use crate::{CachedRowCursor, RowFramer};
+
+// Row read by `Rows`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Row {
+    // Row number
+    pub index: u64,
+    // Byte offset the row was read from
+    pub offset: u64,
+    pub bytes: Vec<u8>,
+}
+
+// Iterator over rows of a cursor, created by `rows` and `rows_range`
+pub struct Rows<'a, T, F: RowFramer> {
+    cursor: &'a mut CachedRowCursor<T, F>,
+    // Row number to stop before
+    end: Option<u64>,
+    done: bool,
+}
+
+impl<T: BufRead + Seek, F: RowFramer> CachedRowCursor<T, F> {
+    // Iterate over rows from the current position. A partly read row yields
+    // only its remaining bytes.
+    pub fn rows(&mut self) -> Rows<'_, T, F> {
+        Rows {
+            cursor: self,
+            end: None,
+            done: false,
+        }
+    }
+
+    // Iterate over rows `range.start` up to `range.end`, seeking to the first
+    // through the index
+    pub fn rows_range(&mut self, range: Range<u64>) -> Result<Rows<'_, T, F>, std::io::Error> {
+        self.set_row_position(range.start)?;
+        Ok(Rows {
+            cursor: self,
+            end: Some(range.end),
+            done: false,
+        })
+    }
+
+    // Return rows `range.start` up to `range.end` and return to the current
+    // position. The index is extended as rows are scanned.
+    pub fn get_rows(&mut self, range: Range<u64>) -> Result<Vec<Row>, std::io::Error> {
+        let mark = self.mark();
+        let rows = self.rows_range(range).and_then(|rows| rows.collect());
+        self.restore(mark)?;
+        rows
+    }
+}
+
+impl<T: BufRead + Seek, F: RowFramer> Iterator for Rows<'_, T, F> {
+    type Item = Result<Row, std::io::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.cursor.row_pos;
+        if self.done || self.end.is_some_and(|end| index >= end) {
+            return None;
+        }
+
+        let offset = self.cursor.pos;
+        let mut bytes = vec![];
+        match self.cursor.read_row(&mut bytes) {
+            Ok(0) => {
+                self.done = true;
+                None
+            }
+            Ok(_) => Some(Ok(Row {
+                index,
+                offset,
+                bytes,
+            })),
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Row;
+    use crate::{CachedRowCursor, LengthHeader};
+    use std::io::{BufReader, Cursor, ErrorKind};
+
+    fn row(index: u64, offset: u64, bytes: &[u8]) -> Row {
+        Row {
+            index,
+            offset,
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    #[test]
+    fn rows() {
+        let data = BufReader::with_capacity(3, Cursor::new(b"foo\nbar\nbiz\nbaz\nbuz"));
+        let mut cursor = CachedRowCursor::new(data, b'\n', 2);
+
+        let rows = cursor.rows().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            rows,
+            [
+                row(0, 0, b"foo\n"),
+                row(1, 4, b"bar\n"),
+                row(2, 8, b"biz\n"),
+                row(3, 12, b"baz\n"),
+                row(4, 16, b"buz"),
+            ]
+        );
+        assert_eq!(cursor.lock_index().row_length, Some(5));
+        assert!(cursor.rows().next().is_none());
+
+        cursor.set_position(9).unwrap();
+        cursor.set_strip_framing(true);
+        let rows = cursor
+            .rows()
+            .take(2)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(rows, [row(2, 9, b"iz"), row(3, 12, b"baz")]);
+    }
+
+    #[test]
+    fn rows_range() {
+        let data = BufReader::new(Cursor::new(b"foo\nbar\nbiz\nbaz\nbuz\n"));
+        let mut cursor = CachedRowCursor::new(data, b'\n', 2);
+
+        let rows = cursor.rows_range(1..3).unwrap();
+        let rows = rows.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(rows, [row(1, 4, b"bar\n"), row(2, 8, b"biz\n")]);
+        assert_eq!(cursor.row_position(), 3);
+
+        let rows = cursor.rows_range(4..10).unwrap();
+        let rows = rows.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(rows, [row(4, 16, b"buz\n")]);
+
+        assert_eq!(cursor.rows_range(7..8).unwrap().count(), 0);
+        assert_eq!(cursor.rows_range(2..2).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn get_rows() {
+        let data = BufReader::with_capacity(3, Cursor::new(b"foo\nbar\nbiz\nbaz\nbuz"));
+        let mut cursor = CachedRowCursor::new(data, b'\n', 2);
+        cursor.set_position(6).unwrap();
+
+        let rows = cursor.get_rows(2..4).unwrap();
+        assert_eq!(rows, [row(2, 8, b"biz\n"), row(3, 12, b"baz\n")]);
+        assert_eq!(cursor.get_rows(4..9).unwrap(), [row(4, 16, b"buz")]);
+        assert!(cursor.get_rows(5..9).unwrap().is_empty());
+        assert_eq!(
+            cursor.lock_index().checkpoints.keys().collect::<Vec<_>>(),
+            [&0, &2, &4]
+        );
+
+        assert_eq!(cursor.position(), 6);
+        assert_eq!(cursor.row_position(), 1);
+        let mut buf = vec![];
+        assert_eq!(cursor.read_row(&mut buf).unwrap(), 2);
+        assert_eq!(buf, b"r\n");
+    }
+
+    #[test]
+    fn error_ends_iteration() {
+        let data = BufReader::new(Cursor::new(b"\x02ab\x09abc"));
+        let mut cursor = CachedRowCursor::with_length_prefix(data, LengthHeader::U8, 1);
+
+        let mut rows = cursor.rows();
+        assert_eq!(rows.next().unwrap().unwrap(), row(0, 0, b"ab"));
+        let err = rows.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert!(rows.next().is_none());
+    }
+}
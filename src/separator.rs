@@ -0,0 +1,293 @@
+use std::ops::Range;
+
+use crate::RowFramer;
+
+// Byte sequence terminating each row
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Separator {
+    kind: Kind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Kind {
+    Bytes {
+        bytes: Box<[u8]>,
+        // Length of the longest proper prefix of `bytes[..=i]` that is also its suffix
+        failure: Box<[usize]>,
+    },
+    // Any of `\n`, `\r\n` or a lone `\r`
+    UniversalNewline,
+}
+
+impl Separator {
+    pub fn new(bytes: &[u8]) -> Self {
+        assert!(!bytes.is_empty(), "row separator must not be empty");
+
+        let mut failure = vec![0; bytes.len()];
+        let mut k = 0;
+        for i in 1..bytes.len() {
+            while k > 0 && bytes[i] != bytes[k] {
+                k = failure[k - 1];
+            }
+            if bytes[i] == bytes[k] {
+                k += 1;
+            }
+            failure[i] = k;
+        }
+
+        Self {
+            kind: Kind::Bytes {
+                bytes: bytes.into(),
+                failure: failure.into(),
+            },
+        }
+    }
+
+    // Separator ending rows at `\n`, `\r\n` or a lone `\r`
+    pub fn universal_newline() -> Self {
+        Self {
+            kind: Kind::UniversalNewline,
+        }
+    }
+
+    // Return separator bytes, or `None` for universal newlines
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match &self.kind {
+            Kind::Bytes { bytes, .. } => Some(bytes),
+            Kind::UniversalNewline => None,
+        }
+    }
+
+    // Whether two occurrences of the separator can overlap, e.g. `aa` in `aaa`
+    pub(crate) fn self_overlapping(&self) -> bool {
+        match &self.kind {
+            Kind::Bytes { failure, .. } => failure[failure.len() - 1] > 0,
+            Kind::UniversalNewline => false,
+        }
+    }
+
+    // Number of bytes before the end of a separator needed to recognise it
+    pub(crate) fn lookbehind(&self) -> usize {
+        match &self.kind {
+            Kind::Bytes { bytes, .. } => bytes.len() - 1,
+            Kind::UniversalNewline => 1,
+        }
+    }
+
+    // Number of bytes after the end of a separator needed to recognise it
+    pub(crate) fn lookahead(&self) -> usize {
+        match &self.kind {
+            Kind::Bytes { .. } => 0,
+            // A `\r` ends a row only once the next byte is known not to be `\n`
+            Kind::UniversalNewline => 1,
+        }
+    }
+
+    // Length of the separator at the end of `row`, if any
+    pub(crate) fn suffix_len(&self, row: &[u8]) -> usize {
+        match &self.kind {
+            Kind::Bytes { bytes, .. } if row.ends_with(bytes) => bytes.len(),
+            Kind::Bytes { .. } => 0,
+            Kind::UniversalNewline if row.ends_with(b"\r\n") => 2,
+            Kind::UniversalNewline if row.ends_with(b"\r") || row.ends_with(b"\n") => 1,
+            Kind::UniversalNewline => 0,
+        }
+    }
+
+    // Search `haystack` for the end of the next separator. `matched` carries the
+    // state of a partial match across calls, so a separator may straddle buffers.
+    // Returns the index just past the separator, which is 0 when the separator
+    // was already consumed but only confirmed by the first byte of `haystack`.
+    pub(crate) fn find(&self, matched: &mut usize, haystack: &[u8]) -> Option<usize> {
+        match &self.kind {
+            Kind::Bytes { bytes, failure } => find_bytes(bytes, failure, matched, haystack),
+            Kind::UniversalNewline => find_newline(matched, haystack),
+        }
+    }
+
+    // Search `haystack` backwards for the end of the last separator ending before
+    // `limit`, ignoring separators that would need bytes beyond `haystack`.
+    // Matches of self-overlapping separators depend on where a forward search
+    // starts, so those are not supported.
+    pub(crate) fn rfind(&self, haystack: &[u8], limit: usize) -> Option<usize> {
+        match &self.kind {
+            Kind::Bytes { bytes, .. } => memchr::memmem::rfind_iter(haystack, bytes)
+                .map(|i| i + bytes.len())
+                .find(|&end| end < limit),
+            // A `\r` ends a row only if the next byte is known not to be `\n`
+            Kind::UniversalNewline => memchr::memrchr2_iter(b'\n', b'\r', haystack)
+                .filter(|&i| {
+                    haystack[i] == b'\n' || haystack.get(i + 1).is_some_and(|&b| b != b'\n')
+                })
+                .map(|i| i + 1)
+                .find(|&end| end < limit),
+        }
+    }
+}
+
+fn find_bytes(
+    bytes: &[u8],
+    failure: &[usize],
+    matched: &mut usize,
+    haystack: &[u8],
+) -> Option<usize> {
+    let mut i = 0;
+    while i < haystack.len() {
+        if *matched == 0 {
+            // Jump to the next possible start of a separator
+            i += memchr::memchr(bytes[0], &haystack[i..])? + 1;
+            *matched = 1;
+        } else {
+            let b = haystack[i];
+            while *matched > 0 && bytes[*matched] != b {
+                *matched = failure[*matched - 1];
+            }
+            if bytes[*matched] == b {
+                *matched += 1;
+            }
+            i += 1;
+        }
+
+        if *matched == bytes.len() {
+            *matched = 0;
+            return Some(i);
+        }
+    }
+    None
+}
+
+// `matched` is 1 after a `\r` that may still be followed by `\n`
+fn find_newline(matched: &mut usize, haystack: &[u8]) -> Option<usize> {
+    if *matched == 1 {
+        let &b = haystack.first()?;
+        *matched = 0;
+        return Some(if b == b'\n' { 1 } else { 0 });
+    }
+
+    let i = memchr::memchr2(b'\n', b'\r', haystack)?;
+    if haystack[i] == b'\n' {
+        return Some(i + 1);
+    }
+    match haystack.get(i + 1) {
+        Some(b'\n') => Some(i + 2),
+        Some(_) => Some(i + 1),
+        None => {
+            *matched = 1;
+            None
+        }
+    }
+}
+
+impl RowFramer for Separator {
+    // Length of a partial separator match, or 1 after a `\r` for universal newlines
+    type State = usize;
+
+    fn find_row_end(
+        &self,
+        matched: &mut usize,
+        buf: &[u8],
+    ) -> Result<Option<usize>, std::io::Error> {
+        Ok(self.find(matched, buf))
+    }
+
+    fn payload(&self, row: &[u8]) -> Range<usize> {
+        0..row.len() - self.suffix_len(row)
+    }
+}
+
+impl From<u8> for Separator {
+    fn from(byte: u8) -> Self {
+        Self::new(&[byte])
+    }
+}
+
+impl From<&[u8]> for Separator {
+    fn from(bytes: &[u8]) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl<const N: usize> From<&[u8; N]> for Separator {
+    fn from(bytes: &[u8; N]) -> Self {
+        Self::new(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Separator;
+
+    fn find_all(separator: &Separator, chunks: &[&[u8]]) -> Vec<usize> {
+        let mut matched = 0;
+        let mut ends = vec![];
+        let mut offset = 0;
+        for chunk in chunks {
+            let mut i = 0;
+            while let Some(end) = separator.find(&mut matched, &chunk[i..]) {
+                i += end;
+                ends.push(offset + i);
+            }
+            offset += chunk.len();
+        }
+        ends
+    }
+
+    #[test]
+    fn find() {
+        let crlf = Separator::from(b"\r\n");
+        assert_eq!(find_all(&crlf, &[b"a\r\nb\r\r\n\n"]), [3, 7]);
+        assert_eq!(find_all(&crlf, &[b"a\r", b"\nb\r", b"\r", b"\n"]), [3, 7]);
+
+        let record = Separator::from(b"||\n");
+        assert_eq!(find_all(&record, &[b"a|||\nb||", b"|\n"]), [5, 10]);
+        assert_eq!(find_all(&record, &[b"a|", b"|", b"\n||\n"]), [4, 7]);
+    }
+
+    #[test]
+    fn universal_newline() {
+        let newline = Separator::universal_newline();
+        assert_eq!(
+            find_all(&newline, &[b"a\nb\r\nc\rd\r\r\n\n"]),
+            [2, 5, 7, 9, 11, 12]
+        );
+        assert_eq!(
+            find_all(&newline, &[b"a\r", b"\nb\r", b"c\r", b"\r", b"\n"]),
+            [3, 5, 7, 9]
+        );
+
+        assert_eq!(newline.suffix_len(b"a\r\n"), 2);
+        assert_eq!(newline.suffix_len(b"a\r"), 1);
+        assert_eq!(newline.suffix_len(b"a\n"), 1);
+        assert_eq!(newline.suffix_len(b"a"), 0);
+    }
+
+    #[test]
+    fn rfind() {
+        let crlf = Separator::from(b"\r\n");
+        assert_eq!(crlf.rfind(b"a\r\nb\r\r\n\n", 8), Some(7));
+        assert_eq!(crlf.rfind(b"a\r\nb\r\r\n\n", 7), Some(3));
+        assert_eq!(crlf.rfind(b"a\r\nb\r\r\n\n", 3), None);
+        assert_eq!(crlf.rfind(b"a\r", 3), None);
+
+        let newline = Separator::universal_newline();
+        assert_eq!(newline.rfind(b"a\nb\r\nc\rd", 11), Some(7));
+        assert_eq!(newline.rfind(b"a\nb\r\nc\r", 9), Some(5));
+        assert_eq!(newline.rfind(b"a\nb\r\nc\r", 5), Some(2));
+        assert_eq!(newline.rfind(b"a\nb\r", 5), Some(2));
+    }
+
+    #[test]
+    fn self_overlapping() {
+        let aa = Separator::from(b"aa");
+        assert!(aa.self_overlapping());
+        assert_eq!(find_all(&aa, &[b"aaaaa"]), [2, 4]);
+        assert_eq!(find_all(&aa, &[b"a", b"aa", b"aa"]), [2, 4]);
+
+        let aba = Separator::from(b"aba");
+        assert!(aba.self_overlapping());
+        assert_eq!(find_all(&aba, &[b"ababab", b"a"]), [3, 7]);
+
+        assert!(!Separator::from(b"\r\n").self_overlapping());
+        assert!(!Separator::from(b'\n').self_overlapping());
+    }
+}
@@ -0,0 +1,419 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, Write};
+use std::path::Path;
+
+use crate::{CachedRowCursor, RowFramer, Separator};
+
+const MAGIC: &[u8; 4] = b"CRCX";
+const VERSION: u32 = 1;
+
+// Number of bytes hashed at each end of the data when fingerprinting it for
+// an index file or for validation
+const FINGERPRINT_SPAN: u64 = 4096;
+
+// Upper bound on the stored separator length, guarding against corrupt files
+const MAX_SEPARATOR_LEN: u64 = 4096;
+
+impl<T: BufRead + Seek> CachedRowCursor<T> {
+    // Save row index to a sidecar file
+    pub fn save_index<P: AsRef<Path>>(&mut self, path: P) -> Result<(), std::io::Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_index(&mut writer)?;
+        writer.flush()
+    }
+
+    // Write row index along with the size and fingerprint of the data
+    pub fn write_index<W: Write>(&mut self, mut writer: W) -> Result<(), std::io::Error> {
+        let (size, fingerprint) = self.fingerprint()?;
+        let index = self.lock_index().clone();
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        write_u64(&mut writer, size)?;
+        write_u64(&mut writer, fingerprint)?;
+        write_u64(&mut writer, self.granularity)?;
+        match self.framer.as_bytes() {
+            Some(separator) => {
+                write_u64(&mut writer, separator.len() as u64)?;
+                writer.write_all(separator)?;
+            }
+            // Stored as an empty separator, which is otherwise invalid
+            None => write_u64(&mut writer, 0)?,
+        }
+        write_u64(&mut writer, self.header.len() as u64)?;
+        write_option(&mut writer, index.length)?;
+        write_option(&mut writer, index.row_length)?;
+        write_u64(&mut writer, index.checkpoints.len() as u64)?;
+        for (row, byte) in index.checkpoints() {
+            write_u64(&mut writer, row)?;
+            write_u64(&mut writer, byte)?;
+        }
+
+        Ok(())
+    }
+
+    // Create cursor with row index loaded from a sidecar file
+    pub fn open_with_index<P: AsRef<Path>>(reader: T, path: P) -> Result<Self, std::io::Error> {
+        Self::from_index(reader, BufReader::new(File::open(path)?))
+    }
+
+    // Create cursor with row index read from `index`, which must match the data in `reader`
+    pub fn from_index<R: Read>(reader: T, mut index: R) -> Result<Self, std::io::Error> {
+        let mut magic = [0; 4];
+        index.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a row index file"));
+        }
+
+        let mut version = [0; 4];
+        index.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version != VERSION {
+            return Err(invalid_data("unsupported row index version"));
+        }
+
+        let size = read_u64(&mut index)?;
+        let fingerprint = read_u64(&mut index)?;
+        let granularity = read_u64(&mut index)?;
+        if granularity == 0 {
+            return Err(invalid_data("invalid row index granularity"));
+        }
+        let separator_len = read_u64(&mut index)?;
+        if separator_len > MAX_SEPARATOR_LEN {
+            return Err(invalid_data("invalid row separator"));
+        }
+        let mut separator = vec![0; separator_len as usize];
+        index.read_exact(&mut separator)?;
+        let separator = if separator.is_empty() {
+            Separator::universal_newline()
+        } else {
+            Separator::new(&separator)
+        };
+        let header_rows = read_u64(&mut index)?;
+        let length = read_option(&mut index)?;
+        let row_length = read_option(&mut index)?;
+
+        let count = read_u64(&mut index)?;
+        let mut cached_index = BTreeMap::new();
+        for _ in 0..count {
+            let row = read_u64(&mut index)?;
+            let byte = read_u64(&mut index)?;
+            cached_index.insert(row, byte);
+        }
+
+        let mut cursor = Self::new(reader, separator, granularity);
+        if cursor.fingerprint()? != (size, fingerprint) {
+            return Err(invalid_data("row index does not match data"));
+        }
+        let consistent = match (length, row_length) {
+            (Some(length), Some(_)) => length == size,
+            (None, None) => true,
+            _ => false,
+        };
+        // Rows and bytes increase together, ending at the end of the data if known
+        let mut points = cached_index
+            .iter()
+            .map(|(&row, &byte)| (row, byte))
+            .collect::<Vec<_>>();
+        if let Some(end) = row_length.zip(length) {
+            if points.last() != Some(&end) {
+                points.push(end);
+            }
+        }
+        let ordered = points
+            .windows(2)
+            .all(|pair| pair[0].0 < pair[1].0 && pair[0].1 < pair[1].1);
+        if !consistent
+            || !ordered
+            || cached_index
+                .iter()
+                .any(|(&row, &byte)| !row.is_multiple_of(granularity) || byte > size)
+        {
+            return Err(invalid_data("inconsistent row index"));
+        }
+        let cursor = cursor.with_header_rows(header_rows)?;
+        let mut index = cursor.lock_index_mut();
+        if cached_index
+            .get(&0)
+            .is_some_and(|&byte| byte != index.origin)
+        {
+            return Err(invalid_data("row index does not match header rows"));
+        }
+
+        for (row, byte) in cached_index {
+            index.insert(row, byte);
+        }
+        index.length = length;
+        index.row_length = row_length;
+        drop(index);
+
+        Ok(cursor)
+    }
+
+    // Return size of the data and a hash of its leading and trailing bytes
+    fn fingerprint(&mut self) -> Result<(u64, u64), std::io::Error> {
+        let size = self.data_end()?;
+        let hash = self.fingerprint_prefix(size)?;
+        Ok((size, hash))
+    }
+}
+
+impl<T: BufRead + Seek, F: RowFramer> CachedRowCursor<T, F> {
+    // Hash the bytes at each end of the data up to `extent`, leaving the
+    // reader at the cursor position
+    pub(crate) fn fingerprint_prefix(&mut self, extent: u64) -> Result<u64, std::io::Error> {
+        let mut hash = Fnv::new();
+        hash.write(&extent.to_le_bytes());
+
+        let mut buf = vec![];
+        let head = extent.min(FINGERPRINT_SPAN);
+        self.read_span(0, head, &mut buf)?;
+        let tail = extent.saturating_sub(FINGERPRINT_SPAN).max(head);
+        self.read_span(tail, extent, &mut buf)?;
+        hash.write(&buf);
+
+        Ok(hash.finish())
+    }
+}
+
+// 64-bit FNV-1a, stable across platforms and compiler versions
+struct Fnv(u64);
+
+impl Fnv {
+    pub(crate) fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+fn invalid_data(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+fn write_u64<W: Write>(writer: &mut W, value: u64) -> Result<(), std::io::Error> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_option<W: Write>(writer: &mut W, value: Option<u64>) -> Result<(), std::io::Error> {
+    writer.write_all(&[value.is_some() as u8])?;
+    write_u64(writer, value.unwrap_or(0))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64, std::io::Error> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_option<R: Read>(reader: &mut R) -> Result<Option<u64>, std::io::Error> {
+    let mut flag = [0; 1];
+    reader.read_exact(&mut flag)?;
+    let value = read_u64(reader)?;
+    match flag[0] {
+        0 => Ok(None),
+        1 => Ok(Some(value)),
+        _ => Err(invalid_data("invalid row index file")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::after_preamble;
+    use crate::{CachedRowCursor, Separator};
+    use std::io::{BufReader, Cursor, ErrorKind, Seek, SeekFrom};
+
+    fn make_cursor(data: &'static [u8]) -> CachedRowCursor<BufReader<Cursor<&'static [u8]>>> {
+        CachedRowCursor::new(BufReader::new(Cursor::new(data)), b'\n', 2)
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut cursor = make_cursor(b"foo\nbar\nbiz\nbaz\nbuz\n");
+        cursor.seek(SeekFrom::End(0)).unwrap();
+        cursor.seek(SeekFrom::Start(5)).unwrap();
+
+        let mut index = vec![];
+        cursor.write_index(&mut index).unwrap();
+        assert_eq!(cursor.position(), 5);
+
+        let data = BufReader::new(Cursor::new(&b"foo\nbar\nbiz\nbaz\nbuz\n"[..]));
+        let mut loaded = CachedRowCursor::from_index(data, &index[..]).unwrap();
+        assert_eq!(*loaded.lock_index(), *cursor.lock_index());
+        assert_eq!(loaded.granularity, 2);
+        assert_eq!(loaded.framer.as_bytes(), Some(&b"\n"[..]));
+        assert_eq!(loaded.lock_index().length, Some(20));
+        assert_eq!(loaded.lock_index().row_length, Some(5));
+        assert_eq!(loaded.position(), 0);
+
+        assert_eq!(loaded.seek_row(SeekFrom::End(0)).unwrap(), 4);
+        assert_eq!(loaded.position(), 16);
+    }
+
+    #[test]
+    fn multi_byte_separator() {
+        let data = &b"foo\r\nbar\r\nbiz\r\n"[..];
+        let mut cursor = CachedRowCursor::new(BufReader::new(Cursor::new(data)), b"\r\n", 1);
+        cursor.seek(SeekFrom::End(0)).unwrap();
+
+        let mut index = vec![];
+        cursor.write_index(&mut index).unwrap();
+
+        let mut loaded =
+            CachedRowCursor::from_index(BufReader::new(Cursor::new(data)), &index[..]).unwrap();
+        assert_eq!(loaded.framer.as_bytes(), Some(&b"\r\n"[..]));
+        assert_eq!(*loaded.lock_index(), *cursor.lock_index());
+        assert_eq!(loaded.seek_row(SeekFrom::End(0)).unwrap(), 2);
+        assert_eq!(loaded.position(), 10);
+    }
+
+    #[test]
+    fn universal_newline() {
+        let data = &b"foo\r\nbar\rbiz\n"[..];
+        let separator = Separator::universal_newline();
+        let mut cursor = CachedRowCursor::new(BufReader::new(Cursor::new(data)), separator, 1);
+        cursor.seek(SeekFrom::End(0)).unwrap();
+
+        let mut index = vec![];
+        cursor.write_index(&mut index).unwrap();
+
+        let loaded =
+            CachedRowCursor::from_index(BufReader::new(Cursor::new(data)), &index[..]).unwrap();
+        assert_eq!(loaded.framer, cursor.framer);
+        assert_eq!(*loaded.lock_index(), *cursor.lock_index());
+        assert_eq!(loaded.lock_index().row_length, Some(3));
+    }
+
+    #[test]
+    fn header_rows() {
+        let data = &b"id\nfoo\nbar\nbiz\n"[..];
+        let cursor = CachedRowCursor::new(BufReader::new(Cursor::new(data)), b'\n', 1);
+        let mut cursor = cursor.with_header_rows(1).unwrap();
+        cursor.seek(SeekFrom::End(0)).unwrap();
+
+        let mut index = vec![];
+        cursor.write_index(&mut index).unwrap();
+
+        let mut loaded =
+            CachedRowCursor::from_index(BufReader::new(Cursor::new(data)), &index[..]).unwrap();
+        assert_eq!(loaded.header(), [b"id\n"]);
+        assert_eq!(*loaded.lock_index(), *cursor.lock_index());
+        assert_eq!(loaded.lock_index().row_length, Some(3));
+        assert_eq!(loaded.seek_row(SeekFrom::Start(1)).unwrap(), 1);
+        assert_eq!(loaded.position(), 7);
+    }
+
+    #[test]
+    fn mismatched_data() {
+        let mut cursor = make_cursor(b"foo\nbar\nbiz\nbaz\nbuz\n");
+        cursor.seek(SeekFrom::End(0)).unwrap();
+        let mut index = vec![];
+        cursor.write_index(&mut index).unwrap();
+
+        let resized = BufReader::new(Cursor::new(&b"foo\nbar\n"[..]));
+        let err = CachedRowCursor::from_index(resized, &index[..])
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let changed = BufReader::new(Cursor::new(&b"foo\nbar\nbiz\nbaz\nbuzz"[..]));
+        let err = CachedRowCursor::from_index(changed, &index[..])
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn inconsistent_index() {
+        let data = &b"foo\nbar\nbiz\nbaz\nbuz\n"[..];
+        let mut cursor = make_cursor(data);
+        cursor.seek(SeekFrom::End(0)).unwrap();
+        let mut index = vec![];
+        cursor.write_index(&mut index).unwrap();
+        let load = |index: &[u8]| {
+            CachedRowCursor::from_index(BufReader::new(Cursor::new(data)), index)
+                .err()
+                .unwrap()
+                .kind()
+        };
+
+        // Offset of the stored data length, after a one-byte separator
+        let length = 49;
+        let mut unset_length = index.clone();
+        unset_length[length] = 0;
+        assert_eq!(load(&unset_length), ErrorKind::InvalidData);
+
+        let mut wrong_length = index.clone();
+        wrong_length[length + 1] = 19;
+        assert_eq!(load(&wrong_length), ErrorKind::InvalidData);
+
+        let last = index.len() - 16;
+        let mut off_granularity = index.clone();
+        off_granularity[last] = 3;
+        assert_eq!(load(&off_granularity), ErrorKind::InvalidData);
+
+        let mut past_end = index.clone();
+        past_end[last + 8] = 21;
+        assert_eq!(load(&past_end), ErrorKind::InvalidData);
+
+        let mut at_end = index.clone();
+        at_end[last + 8] = 20;
+        assert_eq!(load(&at_end), ErrorKind::InvalidData);
+
+        let mut past_last_row = index.clone();
+        past_last_row[last] = 6;
+        assert_eq!(load(&past_last_row), ErrorKind::InvalidData);
+
+        // Row 2 moved to the start of row 4
+        let mut out_of_order = index;
+        out_of_order[last - 8] = 16;
+        assert_eq!(load(&out_of_order), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reader_after_preamble() {
+        let data = b"foo\nbar\nbiz\n";
+        let mut cursor = CachedRowCursor::new(after_preamble(data), b'\n', 1);
+        cursor.seek(SeekFrom::End(0)).unwrap();
+        cursor.seek(SeekFrom::Start(4)).unwrap();
+        let mut index = vec![];
+        cursor.write_index(&mut index).unwrap();
+        let mut buf = vec![];
+        cursor.read_row(&mut buf).unwrap();
+        assert_eq!(buf, b"bar\n");
+
+        // The index matches the same data without the preamble
+        let reader = BufReader::new(Cursor::new(&data[..]));
+        let mut cursor = CachedRowCursor::from_index(reader, &index[..]).unwrap();
+        assert_eq!(cursor.lock_index().row_length, Some(3));
+        cursor.seek_row(SeekFrom::Start(2)).unwrap();
+        buf.clear();
+        cursor.read_row(&mut buf).unwrap();
+        assert_eq!(buf, b"biz\n");
+    }
+
+    #[test]
+    fn invalid_file() {
+        let data = BufReader::new(Cursor::new(&b"foo\n"[..]));
+        let err = CachedRowCursor::from_index(data, &b"CRCX\x02\0\0\0"[..])
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let data = BufReader::new(Cursor::new(&b"foo\n"[..]));
+        let err = CachedRowCursor::from_index(data, &b"foo\n"[..])
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,333 @@
+use std::fmt;
+use std::fs::{File, Metadata};
+use std::io::{BufRead, BufReader, Seek};
+use std::path::Path;
+
+use crate::{CachedRowCursor, Mark, RowFramer, RowIndex};
+
+// Change to the data under a row index, reported by `validate` as an
+// `std::io::Error` of kind `InvalidData`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SourceChanged {
+    // The data is shorter than the indexed part
+    Truncated,
+    // The contents of the indexed part differ
+    Modified,
+    // Another file has taken the place of the file, such as by log rotation
+    Replaced,
+}
+
+impl SourceChanged {
+    // Return the change reported by `err`, if any
+    pub fn from_error(err: &std::io::Error) -> Option<Self> {
+        err.get_ref()?.downcast_ref::<Self>().copied()
+    }
+}
+
+impl fmt::Display for SourceChanged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Truncated => "data is shorter than its row index",
+            Self::Modified => "data does not match its row index",
+            Self::Replaced => "file was replaced",
+        })
+    }
+}
+
+impl std::error::Error for SourceChanged {}
+
+impl From<SourceChanged> for std::io::Error {
+    fn from(change: SourceChanged) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, change)
+    }
+}
+
+// What to do when the data no longer matches the row index
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnSourceChange {
+    // Return a `SourceChanged` error
+    #[default]
+    Error,
+    // Drop the index and start again from the first row
+    Rebuild,
+}
+
+// Fingerprint of the data up to `extent`
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Baseline {
+    extent: u64,
+    fingerprint: u64,
+}
+
+impl<T: BufRead + Seek, F: RowFramer> CachedRowCursor<T, F> {
+    // Validate the data before each `seek_row` and `Seek::seek`, handling
+    // changes as given, or stop validating with `None`. See `validate`.
+    //
+    // Each validation seeks the reader three times, reads up to 8 KiB and
+    // discards the reader's buffer, so seeks in a loop are better validated
+    // by calling `validate` once beforehand.
+    pub fn set_validation(
+        &mut self,
+        on_change: Option<OnSourceChange>,
+    ) -> Result<(), std::io::Error> {
+        self.validation = on_change;
+        if on_change.is_some() && self.baseline.is_none() {
+            self.validate()?;
+        }
+        Ok(())
+    }
+
+    // Check that the data still matches the row index: that it is no shorter
+    // than the indexed part, and that the indexed part has the same
+    // fingerprint as when last validated. The fingerprint covers the start and
+    // end of the indexed part, so changes only in between go unnoticed.
+    //
+    // A change is returned as a `SourceChanged` error. With validation set to
+    // `OnSourceChange::Rebuild`, the index is dropped instead, the cursor moves
+    // to the first row and the change is returned.
+    pub fn validate(&mut self) -> Result<Option<SourceChanged>, std::io::Error> {
+        let extent = self.indexed_extent();
+        let size = self.data_end()?;
+
+        let baseline = self.baseline;
+        let change = match baseline {
+            _ if size < extent => Some(SourceChanged::Truncated),
+            Some(baseline) if size < baseline.extent => Some(SourceChanged::Truncated),
+            Some(baseline) if self.fingerprint_prefix(baseline.extent)? != baseline.fingerprint => {
+                Some(SourceChanged::Modified)
+            }
+            _ => None,
+        };
+
+        match change {
+            Some(change) => self.source_changed(change),
+            None => {
+                self.baseline = Some(Baseline {
+                    extent,
+                    fingerprint: self.fingerprint_prefix(extent)?,
+                });
+                Ok(None)
+            }
+        }
+    }
+
+    // Drop the row index, including rows found by cursors sharing it, and
+    // move to the first row, reading any header rows again
+    pub fn invalidate(&mut self) -> Result<(), std::io::Error> {
+        *self.lock_index_mut() = RowIndex::new(self.granularity);
+        self.baseline = None;
+        self.complete_end = 0;
+        self.jump_to_checkpoint(0, 0)?;
+
+        let rows = self.header.len() as u64;
+        self.header.clear();
+        self.read_header_rows(rows)
+    }
+
+    fn source_changed(
+        &mut self,
+        change: SourceChanged,
+    ) -> Result<Option<SourceChanged>, std::io::Error> {
+        match self.validation.unwrap_or_default() {
+            OnSourceChange::Error => Err(change.into()),
+            OnSourceChange::Rebuild => {
+                self.invalidate()?;
+                Ok(Some(change))
+            }
+        }
+    }
+
+    // End of the data covered by the index and the cursor position
+    fn indexed_extent(&self) -> u64 {
+        let index = self.lock_index();
+        let last = index.checkpoints().next_back().map_or(0, |(_, byte)| byte);
+        index.length.unwrap_or(last).max(self.pos)
+    }
+}
+
+impl<F: RowFramer> CachedRowCursor<BufReader<File>, F> {
+    // Check that the file at `path` is still the file being read, then
+    // validate the data as by `validate`. A file replaced by another, as by
+    // log rotation, is reported as `SourceChanged::Replaced`, or with
+    // validation set to `OnSourceChange::Rebuild` the new file is opened and
+    // read from its first row.
+    pub fn validate_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<Option<SourceChanged>, std::io::Error> {
+        let current = std::fs::metadata(&path)?;
+        if same_file(&self.inner.get_ref().metadata()?, &current) {
+            return self.validate();
+        }
+
+        match self.validation.unwrap_or_default() {
+            OnSourceChange::Error => Err(SourceChanged::Replaced.into()),
+            OnSourceChange::Rebuild => {
+                // The new file is read from its start
+                self.inner = BufReader::new(File::open(path)?);
+                self.set_mark(Mark::checkpoint(0, 0));
+                self.invalidate()?;
+                Ok(Some(SourceChanged::Replaced))
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    (a.dev(), a.ino()) == (b.dev(), b.ino())
+}
+
+// Without file IDs, a file is told apart by its creation time
+#[cfg(not(unix))]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    match (a.created(), b.created()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OnSourceChange, SourceChanged};
+    use crate::test_util::{after_preamble, temp_file};
+    use crate::CachedRowCursor;
+    use std::fs::File;
+    use std::io::{BufReader, Cursor, ErrorKind, Seek, SeekFrom};
+
+    fn make_cursor(data: &[u8]) -> CachedRowCursor<BufReader<Cursor<Vec<u8>>>> {
+        CachedRowCursor::new(BufReader::new(Cursor::new(data.to_vec())), b'\n', 1)
+    }
+
+    #[test]
+    fn unchanged() {
+        let mut cursor = make_cursor(b"foo\nbar\nbiz\n");
+        cursor.set_validation(Some(OnSourceChange::Error)).unwrap();
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 2);
+        assert_eq!(cursor.seek_row(SeekFrom::Start(1)).unwrap(), 1);
+
+        // Growth is not a change
+        let data = cursor.inner.get_mut().get_mut();
+        data.extend_from_slice(b"baz\n");
+        assert_eq!(cursor.validate().unwrap(), None);
+        assert_eq!(cursor.position(), 4);
+    }
+
+    #[test]
+    fn truncated() {
+        let mut cursor = make_cursor(b"foo\nbar\nbiz\n");
+        cursor.set_validation(Some(OnSourceChange::Error)).unwrap();
+        cursor.seek_row(SeekFrom::End(0)).unwrap();
+
+        cursor.inner.get_mut().get_mut().truncate(6);
+        let err = cursor.seek_row(SeekFrom::Start(1)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            SourceChanged::from_error(&err),
+            Some(SourceChanged::Truncated)
+        );
+        let err = cursor.seek(SeekFrom::Start(0)).unwrap_err();
+        assert_eq!(
+            SourceChanged::from_error(&err),
+            Some(SourceChanged::Truncated)
+        );
+        assert_eq!(cursor.position(), 8);
+    }
+
+    #[test]
+    fn modified() {
+        let mut cursor = make_cursor(b"foo\nbar\nbiz\n");
+        cursor.seek_row(SeekFrom::End(0)).unwrap();
+        assert_eq!(cursor.validate().unwrap(), None);
+
+        cursor.inner.get_mut().get_mut()[..4].copy_from_slice(b"fo\nx");
+        let err = cursor.validate().unwrap_err();
+        assert_eq!(
+            SourceChanged::from_error(&err),
+            Some(SourceChanged::Modified)
+        );
+        assert_eq!(SourceChanged::from_error(&ErrorKind::Other.into()), None);
+    }
+
+    #[test]
+    fn rebuild() {
+        let data = BufReader::new(Cursor::new(b"id\nfoo\nbar\nbiz\n".to_vec()));
+        let cursor = CachedRowCursor::new(data, b'\n', 1);
+        let mut cursor = cursor.with_header_rows(1).unwrap();
+        cursor
+            .set_validation(Some(OnSourceChange::Rebuild))
+            .unwrap();
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 2);
+
+        *cursor.inner.get_mut().get_mut() = b"key\nfoobar\nbaz\n".to_vec();
+        assert_eq!(cursor.seek_row(SeekFrom::Start(1)).unwrap(), 1);
+        assert_eq!(cursor.position(), 11);
+        assert_eq!(cursor.header(), [b"key\n"]);
+        assert_eq!(cursor.lock_index().row_length, None);
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 1);
+    }
+
+    #[test]
+    fn reader_after_preamble() {
+        let mut cursor = CachedRowCursor::new(after_preamble(b"foo\nbar\nbiz\n"), b'\n', 1);
+        assert_eq!(cursor.validate().unwrap(), None);
+        let mut buf = vec![];
+        cursor.read_row(&mut buf).unwrap();
+        assert_eq!(buf, b"foo\n");
+
+        cursor.set_validation(Some(OnSourceChange::Error)).unwrap();
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 2);
+        assert_eq!(cursor.seek_row(SeekFrom::Start(1)).unwrap(), 1);
+        assert_eq!(cursor.seek(SeekFrom::Current(1)).unwrap(), 5);
+        buf.clear();
+        cursor.read_row(&mut buf).unwrap();
+        assert_eq!(buf, b"ar\n");
+
+        // Bytes before the reader's start are not part of the data
+        cursor.inner.get_mut().get_mut()[..8].copy_from_slice(b"preamble");
+        assert_eq!(cursor.validate().unwrap(), None);
+
+        cursor.inner.get_mut().get_mut()[9..12].copy_from_slice(b"FOO");
+        cursor
+            .set_validation(Some(OnSourceChange::Rebuild))
+            .unwrap();
+        let change = cursor.validate().unwrap();
+        assert_eq!(change, Some(SourceChanged::Modified));
+        assert_eq!(cursor.position(), 0);
+        buf.clear();
+        cursor.read_row(&mut buf).unwrap();
+        assert_eq!(buf, b"FOO\n");
+    }
+
+    #[test]
+    fn replaced_file() {
+        let path = temp_file("source", b"foo\nbar\n");
+        let rotated = path.with_extension("1");
+
+        let file = BufReader::new(File::open(&path).unwrap());
+        let mut cursor = CachedRowCursor::new(file, b'\n', 1);
+        assert_eq!(cursor.seek_row(SeekFrom::End(0)).unwrap(), 1);
+        assert_eq!(cursor.validate_file(&path).unwrap(), None);
+
+        std::fs::rename(&path, &rotated).unwrap();
+        std::fs::write(&path, b"biz\n").unwrap();
+        let err = cursor.validate_file(&path).unwrap_err();
+        assert_eq!(
+            SourceChanged::from_error(&err),
+            Some(SourceChanged::Replaced)
+        );
+
+        cursor
+            .set_validation(Some(OnSourceChange::Rebuild))
+            .unwrap();
+        let change = cursor.validate_file(&path).unwrap();
+        assert_eq!(change, Some(SourceChanged::Replaced));
+        let mut buf = vec![];
+        cursor.read_row(&mut buf).unwrap();
+        assert_eq!(buf, b"biz\n");
+
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(rotated).unwrap();
+    }
+}
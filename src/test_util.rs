@@ -0,0 +1,49 @@
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+// Write `data` to a file in the temporary directory named after the test
+// process and `name`, returning its path
+pub(crate) fn temp_file(name: &str, data: &[u8]) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("cached_row_cursor-{}-{}", std::process::id(), name));
+    std::fs::write(&path, data).unwrap();
+    path
+}
+
+// Reader over `data` that starts past a preamble, as positions are counted
+// from where the reader starts
+pub(crate) fn after_preamble(data: &[u8]) -> BufReader<Cursor<Vec<u8>>> {
+    let preamble = b"PREAMBLE\n";
+    let mut inner = Cursor::new([&preamble[..], data].concat());
+    inner.set_position(preamble.len() as u64);
+    BufReader::new(inner)
+}
+
+// Reader counting the bytes read through it
+pub(crate) struct Counting {
+    inner: Cursor<Vec<u8>>,
+    pub(crate) read: usize,
+}
+
+impl Counting {
+    pub(crate) fn new(data: Vec<u8>) -> Self {
+        Self {
+            inner: Cursor::new(data),
+            read: 0,
+        }
+    }
+}
+
+impl Read for Counting {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n;
+        Ok(n)
+    }
+}
+
+impl Seek for Counting {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}